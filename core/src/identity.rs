@@ -0,0 +1,63 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::prelude::Uuid;
+
+pub const PATH_SEPARATOR: char = '/';
+
+/// Root namespace for uuids derived from plain names.
+pub const TAG_NAMESPACE: Uuid = uuid::uuid!("6e412385-afff-40bc-8a2f-a031d174201c");
+
+pub fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split(PATH_SEPARATOR).filter(|x| !x.is_empty())
+}
+
+/// Drop empty segments, so `/a//b/` and `a/b` are the same path.
+pub fn normalize_path(path: &str) -> String {
+    split_path(path).collect::<Vec<_>>().join(&PATH_SEPARATOR.to_string())
+}
+
+/// Namespace for a volume identified by name rather than uuid.
+pub fn namespace_uuid(name: &str) -> Uuid {
+    Uuid::new_v5(&TAG_NAMESPACE, name.as_bytes())
+}
+
+/// Stable uuid of the tag at `path` within a namespace, usually a volume's uuid.
+pub fn path_uuid(namespace: &Uuid, path: &str) -> Uuid {
+    Uuid::new_v5(namespace, normalize_path(path).as_bytes())
+}
+
+/// Stable uuid of the parent of the tag at `path`, None for top level paths.
+pub fn parent_path_uuid(namespace: &Uuid, path: &str) -> Option<Uuid> {
+    let path = normalize_path(path);
+    path.rsplit_once(PATH_SEPARATOR)
+        .map(|(parent, _)| path_uuid(namespace, parent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_uuids_are_stable() {
+        let library = namespace_uuid("library");
+        assert_eq!(library, uuid::uuid!("dd289007-a080-52cb-9392-8f8f7910e978"));
+        assert_eq!(path_uuid(&library, "music/jazz"), uuid::uuid!("086a18d6-7b7c-58c6-a5ca-16868f560bef"));
+        assert_eq!(path_uuid(&library, "/music//jazz/"), path_uuid(&library, "music/jazz"));
+    }
+
+    #[test]
+    fn path_uuids_differ() {
+        let library = namespace_uuid("library");
+        assert_ne!(path_uuid(&library, "music/jazz"), path_uuid(&library, "music/rock"));
+        assert_ne!(path_uuid(&library, "music/jazz"), path_uuid(&namespace_uuid("other"), "music/jazz"));
+        assert_ne!(path_uuid(&library, "music"), path_uuid(&library, "music/jazz"));
+    }
+
+    #[test]
+    fn parent_path_uuids() {
+        let library = namespace_uuid("library");
+        assert_eq!(parent_path_uuid(&library, "music/jazz"), Some(path_uuid(&library, "music")));
+        assert_eq!(parent_path_uuid(&library, "/music/"), None);
+    }
+}
@@ -1,6 +1,14 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod tag;
+pub mod identity;
 
 pub mod prelude {
     #[doc(hidden)]
     pub use crate::tag::{Uuid, Tag};
-}
\ No newline at end of file
+
+    #[doc(hidden)]
+    pub use crate::identity::{namespace_uuid, path_uuid, parent_path_uuid};
+}
@@ -0,0 +1,171 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitStr, Path, Result};
+
+/// Implement `tag_core::Tag` and `tag_proto::Tag` for a struct.
+///
+/// The uuid field is the one marked `#[tag(uuid)]`, or else the field named `uuid`.
+/// The parent field is the one marked `#[tag(parent)]`, or else the field named
+/// `parent`, it must be an `Option<Uuid>`. Without a parent field the tag is a root.
+/// A `TagMeta` field marked `#[tag(meta)]` is exposed as the tag's metadata.
+///
+/// Struct level options:
+/// - `#[tag(crate = "tag_model::tag_proto")]` to reach `tag_proto` through another path
+/// - `#[tag(serde)]` to also implement `SerdeTagType` and register the struct,
+///   named after the struct, or `#[tag(serde = "Name")]` to pick the stored name,
+///   the struct also needs `Clone`, `PartialEq` and serde's traits
+#[proc_macro_derive(Tag, attributes(tag))]
+pub fn derive_tag(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+struct StructArgs {
+    krate: Path,
+    serde: Option<Option<LitStr>>,
+}
+
+fn parse_struct_args(input: &DeriveInput) -> Result<StructArgs> {
+    let mut args = StructArgs {
+        krate: syn::parse_quote!(::tag_proto),
+        serde: None,
+    };
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("tag")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                let path: LitStr = meta.value()?.parse()?;
+                args.krate = path.parse()?;
+                Ok(())
+            } else if meta.path.is_ident("serde") {
+                if meta.input.peek(syn::Token![=]) {
+                    args.serde = Some(Some(meta.value()?.parse()?));
+                } else {
+                    args.serde = Some(None);
+                }
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate` or `serde`"))
+            }
+        })?;
+    }
+    Ok(args)
+}
+
+fn find_field(input: &DeriveInput, marker: &str, by_name: bool) -> Result<Option<Ident>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(input, "#[derive(Tag)] needs named fields")),
+        },
+        _ => return Err(Error::new_spanned(input, "#[derive(Tag)] only supports structs")),
+    };
+    let mut marked = None;
+    for field in fields.iter() {
+        for attr in field.attrs.iter().filter(|x| x.path().is_ident("tag")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(marker) {
+                    if marked.is_some() {
+                        return Err(meta.error(format!("duplicated #[tag({})]", marker)));
+                    }
+                    marked = field.ident.clone();
+                    Ok(())
+                } else if ["uuid", "parent", "meta"].iter().any(|x| meta.path.is_ident(x)) {
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `uuid`, `parent` or `meta`"))
+                }
+            })?;
+        }
+    }
+    if marked.is_some() || !by_name {
+        return Ok(marked);
+    }
+    Ok(fields.iter()
+        .filter_map(|x| x.ident.as_ref())
+        .find(|x| *x == marker)
+        .cloned())
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let args = parse_struct_args(&input)?;
+    let uuid = find_field(&input, "uuid", true)?
+        .ok_or_else(|| Error::new_spanned(&input.ident, "missing #[tag(uuid)] field"))?;
+    let parent = find_field(&input, "parent", true)?;
+    let meta = find_field(&input, "meta", false)?;
+
+    let krate = &args.krate;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // proto tags are `Any`, so type parameters have to be 'static
+    let mut tag_generics = input.generics.clone();
+    for param in tag_generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!('static));
+    }
+    let (tag_impl_generics, _, _) = tag_generics.split_for_impl();
+
+    let (has_parent, get_parent) = match &parent {
+        Some(parent) => (
+            quote!(self.#parent.is_some()),
+            quote!(self.#parent.as_ref()),
+        ),
+        None => (
+            quote!(false),
+            quote!(None),
+        ),
+    };
+
+    let meta_methods = meta.map(|meta| quote! {
+        fn meta(&self) -> Option<&#krate::prelude::TagMeta> {
+            Some(&self.#meta)
+        }
+
+        fn meta_mut(&mut self) -> Option<&mut #krate::prelude::TagMeta> {
+            Some(&mut self.#meta)
+        }
+    });
+
+    let mut expanded = quote! {
+        impl #impl_generics #krate::prelude::CoreTag for #ident #ty_generics #where_clause {
+            fn uuid(&self) -> &#krate::prelude::Uuid {
+                &self.#uuid
+            }
+
+            fn has_parent(&self) -> bool {
+                #has_parent
+            }
+        }
+
+        impl #tag_impl_generics #krate::prelude::Tag for #ident #ty_generics #where_clause {
+            fn parent(&self) -> Option<&#krate::prelude::Uuid> {
+                #get_parent
+            }
+
+            #meta_methods
+        }
+    };
+
+    if let Some(name) = &args.serde {
+        if !input.generics.params.is_empty() {
+            return Err(Error::new_spanned(&input.generics, "#[tag(serde)] doesn't support generic structs"));
+        }
+        let name = match name {
+            Some(name) => name.clone(),
+            None => LitStr::new(&ident.to_string(), ident.span()),
+        };
+        expanded.extend(quote! {
+            impl #krate::prelude::SerdeTagType for #ident {
+                fn tag_type_name() -> #krate::serde::registry::Cow<'static, str> {
+                    #krate::serde::registry::Cow::Borrowed(#name)
+                }
+            }
+
+            #krate::register_serde_tag!(#ident);
+        });
+    }
+
+    Ok(expanded)
+}
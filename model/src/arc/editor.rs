@@ -0,0 +1,379 @@
+use std::sync::{Arc, Weak};
+use std::fmt::Debug;
+use std::future::Future;
+use snafu::prelude::*;
+
+use tag_proto::meta::now;
+
+use super::prelude::{Uuid, Hash, IndexMap, IndexSet, CoreTag, ProtoTag, Timestamp, LoadResult, Tag, Item, Volume};
+
+#[derive(Debug, Snafu)]
+pub enum EditorError {
+    #[snafu(display("Duplicate tag: `{}`", uuid))]
+    DuplicateTag { uuid: Uuid },
+    #[snafu(display("Duplicate item: `{}`", uuid))]
+    DuplicateItem { uuid: Uuid },
+    #[snafu(display("Tag not found: `{}`", uuid))]
+    TagNotFound { uuid: Uuid },
+    #[snafu(display("Item not found: `{}`", uuid))]
+    ItemNotFound { uuid: Uuid },
+    #[snafu(display("Missing parent: `{}` -> `{}`", uuid, parent))]
+    MissingParent { uuid: Uuid, parent: Uuid },
+    #[snafu(display("Not under root: `{}`", uuid))]
+    NotUnderRoot { uuid: Uuid },
+    #[snafu(display("Can not remove root: `{}`", uuid))]
+    RemoveRoot { uuid: Uuid },
+    #[snafu(display("Parent changed: `{}`", uuid))]
+    ParentChanged { uuid: Uuid },
+}
+
+pub type EditorResult<T> = std::result::Result<T, EditorError>;
+
+/// Root tag and items of a volume.
+pub type VolumeParts<TD, ID> = (Arc<Tag<TD, ID>>, IndexMap<Uuid, Arc<Item<TD, ID>>>);
+
+#[derive(Debug, Clone)]
+struct TagEntry<TD> {
+    proto: Arc<dyn ProtoTag + Send + Sync>,
+    data: TD,
+}
+
+/// Tags, items and assignments of a volume, kept as plain indexes so they can
+/// be edited, then built into arc tags and items where all links agree.
+///
+/// Tags are added under a parent that is already there, so the tree is
+/// always complete and free of cycles.
+#[derive(Debug, Clone)]
+pub struct VolumeEditor<TD, ID> {
+    root: Uuid,
+    tags: IndexMap<Uuid, TagEntry<TD>>,
+    items: IndexMap<Uuid, ID>,
+    assignments: IndexMap<Uuid, IndexSet<Uuid>>,
+}
+
+impl<TD: Debug, ID: Debug> VolumeEditor<TD, ID> {
+    pub fn new(root: Arc<dyn ProtoTag + Send + Sync>, data: TD) -> Self {
+        let uuid = *root.uuid();
+        let mut tags = IndexMap::new();
+        tags.insert(uuid, TagEntry { proto: root, data });
+        Self {
+            root: uuid,
+            tags,
+            items: IndexMap::new(),
+            assignments: IndexMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Uuid {
+        &self.root
+    }
+
+    pub fn tags_count(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn items_count(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn contains_tag(&self, uuid: &Uuid) -> bool {
+        self.tags.contains_key(uuid)
+    }
+
+    pub fn contains_item(&self, uuid: &Uuid) -> bool {
+        self.items.contains_key(uuid)
+    }
+
+    pub fn get_proto(&self, uuid: &Uuid) -> Option<&Arc<dyn ProtoTag + Send + Sync>> {
+        self.tags.get(uuid).map(|x| &x.proto)
+    }
+
+    pub fn tag_data_mut(&mut self, uuid: &Uuid) -> Option<&mut TD> {
+        self.tags.get_mut(uuid).map(|x| &mut x.data)
+    }
+
+    pub fn item_data_mut(&mut self, uuid: &Uuid) -> Option<&mut ID> {
+        self.items.get_mut(uuid)
+    }
+
+    /// Protos and data of tags, parents before their children.
+    pub fn tags(&self) -> impl Iterator<Item = (&Arc<dyn ProtoTag + Send + Sync>, &TD)> {
+        self.tags.values().map(|x| (&x.proto, &x.data))
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = (&Uuid, &ID)> {
+        self.items.iter()
+    }
+
+    /// Tags assigned to an item, in assignment order.
+    pub fn item_tags(&self, item: &Uuid) -> Option<&IndexSet<Uuid>> {
+        self.assignments.get(item)
+    }
+
+    /// Add a tag under its parent, which has to be added before.
+    ///
+    /// Metadata is left alone as for loading stored tags, `upsert_tag` stamps it.
+    pub fn add_tag(&mut self, proto: Arc<dyn ProtoTag + Send + Sync>, data: TD) -> EditorResult<()> {
+        let uuid = *proto.uuid();
+        ensure!(!self.tags.contains_key(&uuid), DuplicateTagSnafu { uuid });
+        let parent = *proto.parent().context(NotUnderRootSnafu { uuid })?;
+        ensure!(self.tags.contains_key(&parent), MissingParentSnafu { uuid, parent });
+        self.tags.insert(uuid, TagEntry { proto, data });
+        Ok(())
+    }
+
+    /// Add or replace a tag as an edit, stamping its metadata, see `TagMeta::stamp`.
+    pub fn upsert_tag(&mut self, proto: Box<dyn ProtoTag + Send + Sync>, data: TD) -> EditorResult<()> {
+        self.upsert_tag_at(proto, data, now())
+    }
+
+    /// A replaced tag keeps its place and children, so it has to keep its parent.
+    pub fn upsert_tag_at(&mut self, mut proto: Box<dyn ProtoTag + Send + Sync>, data: TD, at: Timestamp) -> EditorResult<()> {
+        let uuid = *proto.uuid();
+        let Some(entry) = self.tags.get_mut(&uuid) else {
+            if let Some(meta) = proto.meta_mut() {
+                meta.stamp(None, at);
+            }
+            return self.add_tag(Arc::from(proto), data);
+        };
+        ensure!(proto.parent() == entry.proto.parent(), ParentChangedSnafu { uuid });
+        if let Some(meta) = proto.meta_mut() {
+            meta.stamp(entry.proto.meta(), at);
+        }
+        *entry = TagEntry { proto: Arc::from(proto), data };
+        Ok(())
+    }
+
+    /// Remove a tag with all tags under it and their assignments, returning
+    /// the removed uuids.
+    pub fn remove_tag(&mut self, uuid: &Uuid) -> EditorResult<Vec<Uuid>> {
+        ensure!(uuid != &self.root, RemoveRootSnafu { uuid: *uuid });
+        ensure!(self.tags.contains_key(uuid), TagNotFoundSnafu { uuid: *uuid });
+        let children = self.children();
+        let mut removed = IndexSet::from([*uuid]);
+        let mut index = 0;
+        while let Some(parent) = removed.get_index(index) {
+            removed.extend(children.get(parent).into_iter().flatten().copied());
+            index += 1;
+        }
+        self.tags.retain(|x, _| !removed.contains(x));
+        for tags in self.assignments.values_mut() {
+            tags.retain(|x| !removed.contains(x));
+        }
+        Ok(removed.into_iter().collect())
+    }
+
+    pub fn add_item(&mut self, uuid: Uuid, data: ID) -> EditorResult<()> {
+        ensure!(!self.items.contains_key(&uuid), DuplicateItemSnafu { uuid });
+        self.items.insert(uuid, data);
+        self.assignments.insert(uuid, IndexSet::new());
+        Ok(())
+    }
+
+    /// Remove an item with its assignments, returning its data.
+    pub fn remove_item(&mut self, uuid: &Uuid) -> EditorResult<ID> {
+        let data = self.items.shift_remove(uuid).context(ItemNotFoundSnafu { uuid: *uuid })?;
+        self.assignments.shift_remove(uuid);
+        Ok(data)
+    }
+
+    /// Assign a tag to an item, false if it was already assigned.
+    pub fn assign(&mut self, item: &Uuid, tag: &Uuid) -> EditorResult<bool> {
+        ensure!(self.tags.contains_key(tag), TagNotFoundSnafu { uuid: *tag });
+        let tags = self.assignments.get_mut(item).context(ItemNotFoundSnafu { uuid: *item })?;
+        Ok(tags.insert(*tag))
+    }
+
+    /// Remove a tag from an item, false if it wasn't assigned.
+    pub fn unassign(&mut self, item: &Uuid, tag: &Uuid) -> EditorResult<bool> {
+        let tags = self.assignments.get_mut(item).context(ItemNotFoundSnafu { uuid: *item })?;
+        Ok(tags.shift_remove(tag))
+    }
+
+    // children of each tag in editor order
+    fn children(&self) -> IndexMap<Uuid, Vec<Uuid>> {
+        let mut children: IndexMap<Uuid, Vec<Uuid>> = IndexMap::new();
+        for (uuid, entry) in self.tags.iter().filter(|(x, _)| **x != self.root) {
+            if let Some(parent) = entry.proto.parent() {
+                children.entry(*parent).or_default().push(*uuid);
+            }
+        }
+        children
+    }
+
+    /// The volume with the built tags and items, see `build_parts`.
+    pub fn build<VD, Body, Loader, AsyncLoader, TF>(
+        self,
+        uuid: Uuid,
+        data: VD,
+        loader: Loader,
+        async_loader: AsyncLoader,
+    ) -> EditorResult<Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>>
+        where
+            VD: Debug,
+            Loader: Fn(&Hash) -> LoadResult<Body>,
+            AsyncLoader: Fn(&Hash) -> TF,
+            TF: Future<Output = LoadResult<Body>>
+    {
+        let (root, items) = self.build_parts()?;
+        Ok(Volume::new(uuid, data, root, items, loader, async_loader))
+    }
+
+    /// Root tag and items where parents, children, tag items and item tags
+    /// all agree, with items in the order they were added.
+    pub fn build_parts(self) -> EditorResult<VolumeParts<TD, ID>> {
+        let children = self.children();
+        let Self { root, tags, items, assignments } = self;
+        let items: IndexMap<Uuid, Arc<Item<TD, ID>>> = items.into_iter()
+            .map(|(uuid, data)| (uuid, Arc::new(Item { uuid, data, tags: Default::default() })))
+            .collect();
+        let mut tag_items: IndexMap<Uuid, IndexMap<Uuid, Arc<Item<TD, ID>>>> = IndexMap::new();
+        for (item, tags) in assignments.iter() {
+            for tag in tags.iter() {
+                tag_items.entry(*tag).or_default().insert(*item, items[item].clone());
+            }
+        }
+        // parents first, then built the other way round so children are
+        // ready before their parents, without recursion on deep trees
+        let mut order = vec![root];
+        let mut index = 0;
+        while index < order.len() {
+            order.extend(children.get(&order[index]).into_iter().flatten());
+            index += 1;
+        }
+        let mut tags = tags;
+        let mut built: IndexMap<Uuid, Arc<Tag<TD, ID>>> = IndexMap::new();
+        let mut links: IndexMap<Uuid, Weak<Tag<TD, ID>>> = IndexMap::new();
+        for uuid in order.iter().rev() {
+            let Some(TagEntry { proto, data }) = tags.swap_remove(uuid) else {
+                continue;
+            };
+            let tag = Arc::new(Tag {
+                data,
+                proto,
+                parent: Default::default(),
+                children: children.get(uuid).into_iter().flatten()
+                    .filter_map(|x| built.swap_remove(x).map(|tag| (*x, tag)))
+                    .collect(),
+                items: tag_items.swap_remove(uuid).unwrap_or_default(),
+            });
+            for child in tag.children.values() {
+                let _ = child.parent.set(Arc::downgrade(&tag));
+            }
+            links.insert(*uuid, Arc::downgrade(&tag));
+            built.insert(*uuid, tag);
+        }
+        let root = built.swap_remove(&root).context(TagNotFoundSnafu { uuid: root })?;
+        for (uuid, item) in items.iter() {
+            let tags = assignments.get(uuid).into_iter().flatten()
+                .filter_map(|x| links.get(x).map(|tag| (*x, tag.clone())))
+                .collect();
+            let _ = item.tags.set(tags);
+        }
+        Ok((root, items))
+    }
+}
+
+impl<TD: Debug + Clone, ID: Debug + Clone> VolumeEditor<TD, ID> {
+    /// Editor holding copies of the data of a tag tree and items.
+    ///
+    /// Assignments are taken from both sides, so hand wired trees where only
+    /// one side was set come out consistent.
+    pub fn from_parts<'a, I>(root: &Tag<TD, ID>, items: I) -> EditorResult<Self>
+        where
+            I: IntoIterator<Item = &'a Arc<Item<TD, ID>>>,
+            TD: 'a,
+            ID: 'a,
+    {
+        let mut editor = Self::new(root.proto.clone(), root.data.clone());
+        let mut stack: Vec<&Tag<TD, ID>> = root.children.values().rev().map(|x| x.as_ref()).collect();
+        let mut tag_items = vec![root];
+        while let Some(tag) = stack.pop() {
+            editor.add_tag(tag.proto.clone(), tag.data.clone())?;
+            stack.extend(tag.children.values().rev().map(|x| x.as_ref()));
+            tag_items.push(tag);
+        }
+        for item in items {
+            editor.add_item(item.uuid, item.data.clone())?;
+            for tag in item.tag_uuids() {
+                editor.assign(&item.uuid, tag)?;
+            }
+        }
+        for tag in tag_items {
+            for (uuid, item) in tag.items.iter() {
+                if !editor.contains_item(uuid) {
+                    editor.add_item(*uuid, item.data.clone())?;
+                }
+                editor.assign(uuid, tag.uuid())?;
+            }
+        }
+        Ok(editor)
+    }
+}
+
+impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+    where
+        TD: Debug + Clone,
+        ID: Debug + Clone,
+        VD: Debug,
+        Loader: Fn(&Hash) -> LoadResult<Body>,
+        AsyncLoader: Fn(&Hash) -> TF,
+        TF: Future<Output = LoadResult<Body>>
+{
+    /// Editor with copies of the tags and items of this volume.
+    pub fn editor(&self) -> EditorResult<VolumeEditor<TD, ID>> {
+        VolumeEditor::from_parts(self.root.as_ref(), self.items.values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tag_proto::prelude::{ValTag, TagMeta};
+
+    use super::*;
+    use crate::testing::{tag, assign};
+
+    fn with_meta(uuid: u128, parent: Option<u128>, meta: TagMeta) -> Box<dyn ProtoTag + Send + Sync> {
+        Box::new(ValTag { meta, ..ValTag::clone(&tag(uuid, parent)) })
+    }
+
+    fn tree() -> VolumeEditor<(), ()> {
+        let mut editor = VolumeEditor::new(tag(1, None), ());
+        for (uuid, parent) in [(2, 1), (3, 2), (4, 3), (5, 1)] {
+            editor.add_tag(tag(uuid, Some(parent)), ()).unwrap();
+        }
+        assign(&mut editor, &[(10, 3), (10, 5), (11, 4)], ());
+        editor
+    }
+
+    #[test]
+    fn remove_tag_removes_subtree() {
+        let mut editor = tree();
+        let removed = editor.remove_tag(&Uuid::from_u128(2)).unwrap();
+        assert_eq!(removed, [2, 3, 4].map(Uuid::from_u128));
+        assert_eq!(editor.tags_count(), 2);
+        assert_eq!(editor.item_tags(&Uuid::from_u128(10)).map(|x| x.len()), Some(1));
+        assert_eq!(editor.item_tags(&Uuid::from_u128(11)).map(|x| x.len()), Some(0));
+        assert!(matches!(editor.remove_tag(&Uuid::from_u128(1)), Err(EditorError::RemoveRoot { .. })));
+    }
+
+    #[test]
+    fn build_with_async_loader() {
+        // async blocks aren't `Clone`, which the volume builder would need
+        let volume = tree().build(Uuid::from_u128(99), (), |_: &Hash| Ok(()), |_: &Hash| async { Ok(()) }).unwrap();
+        assert_eq!(volume.uuid, Uuid::from_u128(99));
+        assert_eq!(volume.root.children.len(), 2);
+        assert_eq!(volume.items.len(), 2);
+        assert_eq!(volume.items[&Uuid::from_u128(10)].tags().count(), 2);
+    }
+
+    #[test]
+    fn upsert_stamps_tags() {
+        let mut editor: VolumeEditor<(), ()> = VolumeEditor::new(tag(1, None), ());
+        editor.upsert_tag_at(with_meta(2, Some(1), TagMeta::default()), (), 10).unwrap();
+        editor.upsert_tag_at(with_meta(2, Some(1), TagMeta::default().with_author("ann")), (), 20).unwrap();
+        let meta = editor.get_proto(&Uuid::from_u128(2)).and_then(|x| x.meta()).cloned();
+        assert_eq!(meta, Some(TagMeta { created: Some(10), modified: Some(20), author: Some("ann".to_string()), source: None }));
+        assert!(matches!(editor.upsert_tag_at(with_meta(2, None, TagMeta::default()), (), 30), Err(EditorError::ParentChanged { .. })));
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt::Debug;
+use std::future::Future;
+use snafu::prelude::*;
+
+use tag_proto::serde::registry::{to_serde_tag, into_shared_tag};
+
+use super::prelude::{Uuid, Hash, IndexMap, SerdeTag, SerdeTags, LoadResult, Assignment, Volume, VolumeEditor, EditorError, EditorResult};
+
+#[derive(Debug, Snafu)]
+pub enum HydrateError {
+    #[snafu(display("Root not found: `{}`", uuid))]
+    RootNotFound { uuid: Uuid },
+    #[snafu(display("Not shareable: `{}` -> {}", uuid, info))]
+    NotShareable { uuid: Uuid, info: String },
+    #[snafu(display("Not serializable: `{}` -> {}", uuid, info))]
+    NotSerializable { uuid: Uuid, info: String },
+    #[snafu(display("Editor failed: {}", source))]
+    EditorFailed { source: EditorError },
+    #[snafu(display("Build failed: {}", info))]
+    BuildFailed { info: String },
+}
+
+pub type HydrateResult<T> = std::result::Result<T, HydrateError>;
+
+/// A reference that didn't resolve while hydrating, what it belongs to is left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dangling {
+    DuplicateTag { uuid: Uuid },
+    /// The parent is not among the tags, tags under it are reported as unreachable.
+    MissingParent { uuid: Uuid, parent: Uuid },
+    /// Under another root or in a parent cycle.
+    Unreachable { uuid: Uuid },
+    TagData { uuid: Uuid },
+    Assignment { item: Uuid, tag: Uuid },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HydrateReport {
+    pub dangling: Vec<Dangling>,
+}
+
+impl HydrateReport {
+    pub fn is_clean(&self) -> bool {
+        self.dangling.is_empty()
+    }
+}
+
+/// A hydrated volume in an editor, with what was left out of it.
+#[derive(Debug, Clone)]
+pub struct Hydrated<TD, ID, VD> {
+    pub uuid: Uuid,
+    pub data: VD,
+    pub editor: VolumeEditor<TD, ID>,
+    pub report: HydrateReport,
+}
+
+impl<TD: Debug, ID: Debug, VD: Debug> Hydrated<TD, ID, VD> {
+    /// The volume with these loaders, see `VolumeEditor::build`.
+    pub fn build<Body, Loader, AsyncLoader, TF>(self, loader: Loader, async_loader: AsyncLoader) -> EditorResult<Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>>
+        where
+            Loader: Fn(&Hash) -> LoadResult<Body>,
+            AsyncLoader: Fn(&Hash) -> TF,
+            TF: Future<Output = LoadResult<Body>>
+    {
+        self.editor.build(self.uuid, self.data, loader, async_loader)
+    }
+}
+
+/// A volume as plain serializable parts, with parents as uuids and tags as `SerdeTags`.
+///
+/// Tags without an entry in `tag_data` get the default data.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DryVolume<TD, ID, VD> {
+    pub uuid: Uuid,
+    pub data: VD,
+    pub root: Uuid,
+    pub tags: SerdeTags,
+    pub tag_data: IndexMap<Uuid, TD>,
+    pub items: IndexMap<Uuid, ID>,
+    pub assignments: Vec<Assignment>,
+}
+
+impl<TD: Debug + Clone, ID: Debug + Clone, VD> DryVolume<TD, ID, VD> {
+    /// Parts of the tags and items of an editor, tags in editor order so
+    /// parents come before their children.
+    pub fn from_editor(uuid: Uuid, data: VD, editor: &VolumeEditor<TD, ID>) -> HydrateResult<Self> {
+        let mut tags = Vec::with_capacity(editor.tags_count());
+        let mut tag_data = IndexMap::new();
+        for (proto, data) in editor.tags() {
+            let tag = to_serde_tag(proto.as_ref())
+                .map_err(|info| HydrateError::NotSerializable { uuid: *proto.uuid(), info })?;
+            tag_data.insert(*proto.uuid(), data.clone());
+            tags.push(tag);
+        }
+        let mut items = IndexMap::new();
+        let mut assignments = Vec::new();
+        for (uuid, data) in editor.items() {
+            items.insert(*uuid, data.clone());
+            assignments.extend(editor.item_tags(uuid).into_iter().flatten()
+                .map(|tag| Assignment { item: *uuid, tag: *tag }));
+        }
+        Ok(Self {
+            uuid,
+            data,
+            root: *editor.root(),
+            tags: SerdeTags(tags),
+            tag_data,
+            items,
+            assignments,
+        })
+    }
+}
+
+impl<TD: Debug + Default, ID: Debug, VD> DryVolume<TD, ID, VD> {
+    /// Editor with the tags under the root and all items, leaving out and
+    /// reporting whatever doesn't resolve.
+    pub fn hydrate(self) -> HydrateResult<Hydrated<TD, ID, VD>> {
+        let Self { uuid: volume, data: volume_data, root, tags, mut tag_data, items, assignments } = self;
+        let mut report = HydrateReport::default();
+        let mut pending: IndexMap<Uuid, Option<Box<dyn SerdeTag>>> = IndexMap::new();
+        for tag in tags.0 {
+            if pending.contains_key(tag.uuid()) {
+                report.dangling.push(Dangling::DuplicateTag { uuid: *tag.uuid() });
+                continue;
+            }
+            pending.insert(*tag.uuid(), Some(tag));
+        }
+        let mut children: IndexMap<Uuid, Vec<Uuid>> = IndexMap::new();
+        for (uuid, tag) in pending.iter().filter(|(x, _)| **x != root) {
+            if let Some(parent) = tag.as_ref().and_then(|x| x.parent()) {
+                children.entry(*parent).or_default().push(*uuid);
+            }
+        }
+        let shared = |tag: Box<dyn SerdeTag>| {
+            let uuid = *tag.uuid();
+            into_shared_tag(tag).map_err(|info| HydrateError::NotShareable { uuid, info })
+        };
+        let root_tag = pending.get_mut(&root).and_then(Option::take)
+            .context(RootNotFoundSnafu { uuid: root })?;
+        let mut editor = VolumeEditor::new(shared(root_tag)?, tag_data.shift_remove(&root).unwrap_or_default());
+        let mut order = vec![root];
+        let mut index = 0;
+        while index < order.len() {
+            for child in children.get(&order[index]).into_iter().flatten() {
+                if let Some(tag) = pending.get_mut(child).and_then(Option::take) {
+                    let data = tag_data.shift_remove(child).unwrap_or_default();
+                    editor.add_tag(shared(tag)?, data).context(EditorFailedSnafu)?;
+                    order.push(*child);
+                }
+            }
+            index += 1;
+        }
+        for (uuid, tag) in pending.iter() {
+            let Some(tag) = tag else {
+                continue;
+            };
+            match tag.parent() {
+                Some(parent) if !pending.contains_key(parent) => {
+                    report.dangling.push(Dangling::MissingParent { uuid: *uuid, parent: *parent });
+                },
+                _ => report.dangling.push(Dangling::Unreachable { uuid: *uuid }),
+            }
+        }
+        report.dangling.extend(tag_data.into_keys().map(|uuid| Dangling::TagData { uuid }));
+        for (uuid, data) in items {
+            editor.add_item(uuid, data).context(EditorFailedSnafu)?;
+        }
+        for Assignment { item, tag } in assignments {
+            if editor.contains_item(&item) && editor.contains_tag(&tag) {
+                editor.assign(&item, &tag).context(EditorFailedSnafu)?;
+            } else {
+                report.dangling.push(Dangling::Assignment { item, tag });
+            }
+        }
+        Ok(Hydrated { uuid: volume, data: volume_data, editor, report })
+    }
+}
+
+impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+    where
+        TD: Debug + Clone,
+        ID: Debug + Clone,
+        VD: Debug + Clone,
+        Loader: Fn(&Hash) -> LoadResult<Body>,
+        AsyncLoader: Fn(&Hash) -> TF,
+        TF: Future<Output = LoadResult<Body>>
+{
+    /// Serializable parts of this volume, to be hydrated back with `DryVolume::hydrate`.
+    pub fn dehydrate(&self) -> HydrateResult<DryVolume<TD, ID, VD>> {
+        let editor = self.editor().context(EditorFailedSnafu)?;
+        DryVolume::from_editor(self.uuid, self.data.clone(), &editor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tag_proto::prelude::ValTag;
+
+    use super::*;
+    use crate::testing::{self, tag, assign};
+
+    fn dry() -> DryVolume<String, u32, String> {
+        let mut editor = VolumeEditor::new(tag(1, None), "root".to_string());
+        for (uuid, parent) in [(2, 1), (3, 2), (4, 1)] {
+            editor.add_tag(tag(uuid, Some(parent)), uuid.to_string()).unwrap();
+        }
+        assign(&mut editor, &[(10, 3), (10, 4), (11, 2)], 0);
+        DryVolume::from_editor(Uuid::from_u128(99), "volume".to_string(), &editor).unwrap()
+    }
+
+    #[test]
+    fn hydrate_dehydrate_is_identity() {
+        let dry = dry();
+        let volume = testing::hydrate(dry.clone());
+        assert_eq!(volume.dehydrate().unwrap(), dry);
+    }
+
+    #[test]
+    fn dangling_references_are_reported() {
+        let mut dry = dry();
+        dry.tags.0.push(Box::new(ValTag::clone(&tag(5, Some(50)))));
+        dry.tags.0.push(Box::new(ValTag::clone(&tag(6, Some(5)))));
+        dry.assignments.push(Assignment { item: Uuid::from_u128(10), tag: Uuid::from_u128(60) });
+        dry.assignments.push(Assignment { item: Uuid::from_u128(12), tag: Uuid::from_u128(2) });
+        let hydrated = dry.hydrate().unwrap();
+        assert_eq!(hydrated.report.dangling, vec![
+            Dangling::MissingParent { uuid: Uuid::from_u128(5), parent: Uuid::from_u128(50) },
+            Dangling::Unreachable { uuid: Uuid::from_u128(6) },
+            Dangling::Assignment { item: Uuid::from_u128(10), tag: Uuid::from_u128(60) },
+            Dangling::Assignment { item: Uuid::from_u128(12), tag: Uuid::from_u128(2) },
+        ]);
+        assert_eq!(hydrated.editor.tags_count(), 4);
+        assert_eq!(hydrated.editor.item_tags(&Uuid::from_u128(10)).map(|x| x.len()), Some(2));
+    }
+}
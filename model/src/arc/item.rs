@@ -1,18 +1,50 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak, OnceLock};
 use std::fmt::Debug;
 use derive_builder::Builder;
 
-use super::prelude::{Uuid, IndexMap, Tag, ModelItem};
+use super::prelude::{Uuid, IndexMap, CoreTag, Tag, ModelItem};
 
+/// An item in a volume, owned by the volume and the tags it is on.
+///
+/// `tags` are weak back-links, set once after the tags are built.
 #[derive(Clone, Debug, Builder)]
 pub struct Item<TD: Debug, ID: Debug> {
     pub uuid: Uuid,
     pub data: ID,
-    #[builder(default)]
-    pub tags: IndexMap<Uuid, Arc<Tag<TD, ID>>>,
+    #[builder(setter(custom), default)]
+    pub tags: OnceLock<IndexMap<Uuid, Weak<Tag<TD, ID>>>>,
 }
 
-impl<TD: Debug, ID: Debug> ModelItem for Item<TD, ID> {
+impl<TD: Debug, ID: Debug> ItemBuilder<TD, ID> {
+    /// Tags the item is on, kept as weak back-links.
+    pub fn tags<'a, I>(&mut self, tags: I) -> &mut Self
+        where
+            I: IntoIterator<Item = &'a Arc<Tag<TD, ID>>>,
+            TD: 'a,
+            ID: 'a
+    {
+        let tags: IndexMap<Uuid, Weak<Tag<TD, ID>>> = tags.into_iter().map(|x| (*x.uuid(), Arc::downgrade(x))).collect();
+        self.tags = Some(OnceLock::from(tags));
+        self
+    }
+}
+
+impl<TD: Debug, ID: Debug> Item<TD, ID> {
+    pub fn tag_uuids(&self) -> impl Iterator<Item = &Uuid> {
+        self.tags.get().into_iter().flat_map(|x| x.keys())
+    }
+
+    /// Tags on this item that are still alive.
+    pub fn tags(&self) -> impl Iterator<Item = Arc<Tag<TD, ID>>> + '_ {
+        self.tags.get().into_iter().flat_map(|x| x.values()).filter_map(Weak::upgrade)
+    }
+
+    pub fn get_tag(&self, uuid: &Uuid) -> Option<Arc<Tag<TD, ID>>> {
+        self.tags.get()?.get(uuid)?.upgrade()
+    }
+}
+
+impl<TD: Debug + 'static, ID: Debug + 'static> ModelItem for Item<TD, ID> {
     type Data = ID;
     type Tag = Tag<TD, ID>;
 
@@ -25,12 +57,12 @@ impl<TD: Debug, ID: Debug> ModelItem for Item<TD, ID> {
     }
 
     fn tags_count(&self) -> usize {
-        self.tags.len()
+        self.tags.get().map(|x| x.len()).unwrap_or(0)
     }
 
     fn each_tag<F: Fn(&Self::Tag) -> bool>(&self, callback: &F) -> bool {
-        for tag in self.tags.values() {
-            if callback(tag) {
+        for tag in self.tags() {
+            if callback(&tag) {
                 return true;
             }
         }
@@ -1,6 +1,13 @@
 pub mod tag;
 pub mod item;
 pub mod volume;
+pub mod editor;
+
+#[cfg(feature = "serde")]
+pub mod hydrate;
+
+#[cfg(feature = "serde")]
+pub mod serde;
 
 pub mod prelude {
     #[doc(hidden)]
@@ -18,4 +25,15 @@ pub mod prelude {
 
     #[doc(hidden)]
     pub use super::volume::Volume;
+
+    #[doc(hidden)]
+    pub use super::editor::{VolumeEditor, VolumeParts, EditorError, EditorResult};
+
+    #[cfg(feature = "serde")]
+    #[doc(hidden)]
+    pub use super::hydrate::{DryVolume, Hydrated, Dangling, HydrateReport, HydrateError, HydrateResult};
+
+    #[cfg(feature = "json")]
+    #[doc(hidden)]
+    pub use super::serde::{VolumeFileError, VolumeFileResult};
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use std::fmt::Debug;
+use std::future::Future;
+
+use ::serde::{Serialize, Serializer};
+use ::serde::ser::{Error, SerializeStruct};
+
+use tag_proto::serde::registry::to_serde_tag;
+
+use super::prelude::{Uuid, Hash, IndexMap, CoreTag, SerdeTags, LoadResult, Assignment, Tag, Item, Volume};
+
+/// A tag with its data, children and items as uuids, the parent is kept in the proto.
+impl<TD: Debug + Serialize, ID: Debug> Serialize for Tag<TD, ID> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tag = to_serde_tag(self.proto.as_ref()).map_err(S::Error::custom)?;
+        let mut state = serializer.serialize_struct("Tag", 4)?;
+        state.serialize_field("tag", &tag)?;
+        state.serialize_field("data", &self.data)?;
+        state.serialize_field("children", &self.children.keys().collect::<Vec<_>>())?;
+        state.serialize_field("items", &self.items.keys().collect::<Vec<_>>())?;
+        state.end()
+    }
+}
+
+/// An item with its data and tags as uuids.
+impl<TD: Debug, ID: Debug + Serialize> Serialize for Item<TD, ID> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Item", 3)?;
+        state.serialize_field("uuid", &self.uuid)?;
+        state.serialize_field("data", &self.data)?;
+        state.serialize_field("tags", &self.tag_uuids().collect::<Vec<_>>())?;
+        state.end()
+    }
+}
+
+// same layout as `DryVolume`, without copying the data
+#[derive(Serialize)]
+struct DryVolumeRef<'a, TD, ID, VD> {
+    uuid: &'a Uuid,
+    data: &'a VD,
+    root: &'a Uuid,
+    tags: SerdeTags,
+    tag_data: IndexMap<Uuid, &'a TD>,
+    items: IndexMap<Uuid, &'a ID>,
+    assignments: Vec<Assignment>,
+}
+
+/// A whole volume as a `DryVolume`, with tags parents first and assignments
+/// as seen from the items. Read it back as a `DryVolume` and hydrate it.
+impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Serialize for Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+    where
+        TD: Debug + Serialize,
+        ID: Debug + Serialize,
+        VD: Debug + Serialize,
+        Loader: Fn(&Hash) -> LoadResult<Body>,
+        AsyncLoader: Fn(&Hash) -> TF,
+        TF: Future<Output = LoadResult<Body>>
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tags = Vec::new();
+        let mut tag_data = IndexMap::new();
+        let mut stack = vec![self.root.as_ref()];
+        while let Some(tag) = stack.pop() {
+            tags.push(to_serde_tag(tag.proto.as_ref()).map_err(S::Error::custom)?);
+            tag_data.insert(*tag.uuid(), &tag.data);
+            stack.extend(tag.children.values().rev().map(|x| x.as_ref()));
+        }
+        let mut items = IndexMap::new();
+        let mut assignments = Vec::new();
+        for (uuid, item) in self.items.iter() {
+            items.insert(*uuid, &item.data);
+            assignments.extend(item.tag_uuids().map(|tag| Assignment { item: *uuid, tag: *tag }));
+        }
+        DryVolumeRef {
+            uuid: &self.uuid,
+            data: &self.data,
+            root: self.root.uuid(),
+            tags: SerdeTags(tags),
+            tag_data,
+            items,
+            assignments,
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter, Read, Write};
+    use std::path::Path;
+    use snafu::prelude::*;
+
+    use ::serde::Serialize;
+    use ::serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::arc::editor::EditorError;
+    use crate::arc::hydrate::{DryVolume, HydrateError, HydrateReport};
+
+    #[derive(Debug, Snafu)]
+    pub enum VolumeFileError {
+        #[snafu(display("IO failed: {} -> {}", info, error))]
+        IoFailed { error: std::io::Error, info: String },
+        #[snafu(display("Json failed: {}", info))]
+        JsonFailed { info: String },
+        #[snafu(display("Hydrate failed: {}", source))]
+        HydrateFailed { source: HydrateError },
+        #[snafu(display("Dangling references: `{}`", report.dangling.len()))]
+        Dangling { report: HydrateReport },
+        #[snafu(display("Build failed: {}", source))]
+        BuildFailed { source: EditorError },
+    }
+
+    pub type VolumeFileResult<T> = std::result::Result<T, VolumeFileError>;
+
+    fn json_failed(error: serde_json::Error) -> VolumeFileError {
+        VolumeFileError::JsonFailed { info: error.to_string() }
+    }
+
+    impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+        where
+            TD: Debug + Serialize,
+            ID: Debug + Serialize,
+            VD: Debug + Serialize,
+            Loader: Fn(&Hash) -> LoadResult<Body>,
+            AsyncLoader: Fn(&Hash) -> TF,
+            TF: Future<Output = LoadResult<Body>>
+    {
+        pub fn write_json<W: Write>(&self, writer: W) -> VolumeFileResult<()> {
+            serde_json::to_writer(writer, self).map_err(json_failed)
+        }
+
+        /// Write the volume as a single json file, to be read with `Volume::read_file`.
+        pub fn write_file<P: AsRef<Path>>(&self, path: P) -> VolumeFileResult<()> {
+            let path = path.as_ref();
+            let file = File::create(path)
+                .map_err(|error| VolumeFileError::IoFailed { error, info: path.display().to_string() })?;
+            let mut writer = BufWriter::new(file);
+            self.write_json(&mut writer)?;
+            writer.flush()
+                .map_err(|error| VolumeFileError::IoFailed { error, info: path.display().to_string() })
+        }
+    }
+
+    impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+        where
+            TD: Debug + Default + DeserializeOwned,
+            ID: Debug + DeserializeOwned,
+            VD: Debug + DeserializeOwned,
+            Loader: Fn(&Hash) -> LoadResult<Body>,
+            AsyncLoader: Fn(&Hash) -> TF,
+            TF: Future<Output = LoadResult<Body>>
+    {
+        /// Read a volume written by `write_json`, failing on any dangling reference.
+        ///
+        /// Read it as a `DryVolume` to hydrate it leniently instead.
+        pub fn read_json<R: Read>(reader: R, loader: Loader, async_loader: AsyncLoader) -> VolumeFileResult<Self> {
+            let hydrated = DryVolume::read_json(reader)?.hydrate().context(HydrateFailedSnafu)?;
+            ensure!(hydrated.report.is_clean(), DanglingSnafu { report: hydrated.report });
+            hydrated.build(loader, async_loader).context(BuildFailedSnafu)
+        }
+
+        pub fn read_file<P: AsRef<Path>>(path: P, loader: Loader, async_loader: AsyncLoader) -> VolumeFileResult<Self> {
+            let path = path.as_ref();
+            let file = File::open(path)
+                .map_err(|error| VolumeFileError::IoFailed { error, info: path.display().to_string() })?;
+            Self::read_json(BufReader::new(file), loader, async_loader)
+        }
+    }
+
+    impl<TD: DeserializeOwned, ID: DeserializeOwned, VD: DeserializeOwned> DryVolume<TD, ID, VD> {
+        pub fn read_json<R: Read>(reader: R) -> VolumeFileResult<Self> {
+            serde_json::from_reader(reader).map_err(json_failed)
+        }
+
+        pub fn read_file<P: AsRef<Path>>(path: P) -> VolumeFileResult<Self> {
+            let path = path.as_ref();
+            let file = File::open(path)
+                .map_err(|error| VolumeFileError::IoFailed { error, info: path.display().to_string() })?;
+            Self::read_json(BufReader::new(file))
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub use json::*;
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::arc::editor::VolumeEditor;
+    use crate::arc::hydrate::DryVolume;
+    use crate::testing::{tag, load, load_async, build, TestVolume};
+
+    fn read(json: &[u8]) -> VolumeFileResult<TestVolume<String, u32, String>> {
+        Volume::read_json(json, load, load_async)
+    }
+
+    fn editor() -> VolumeEditor<String, u32> {
+        let mut editor = VolumeEditor::new(tag(1, None), "root".to_string());
+        // children out of uuid order, so order has to be kept
+        for (uuid, parent) in [(4, 1), (2, 1), (3, 2), (5, 4)] {
+            editor.add_tag(tag(uuid, Some(parent)), uuid.to_string()).unwrap();
+        }
+        for (item, tags) in [(11, vec![5, 2]), (10, vec![3]), (12, vec![])] {
+            let item = Uuid::from_u128(item);
+            editor.add_item(item, 7).unwrap();
+            for tag in tags {
+                editor.assign(&item, &Uuid::from_u128(tag)).unwrap();
+            }
+        }
+        editor
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let volume = build(editor(), 99, "volume".to_string());
+        let mut json = Vec::new();
+        volume.write_json(&mut json).unwrap();
+        let loaded = read(&json).unwrap();
+        let mut again = Vec::new();
+        loaded.write_json(&mut again).unwrap();
+        assert_eq!(json, again);
+
+        assert_eq!(loaded.uuid, Uuid::from_u128(99));
+        assert_eq!(loaded.data, "volume");
+        assert_eq!(loaded.root.children.keys().copied().collect::<Vec<_>>(), [4, 2].map(Uuid::from_u128));
+        let tag_3 = loaded.root.children[&Uuid::from_u128(2)].children[&Uuid::from_u128(3)].clone();
+        assert_eq!(tag_3.data, "3");
+        assert_eq!(tag_3.parent_tag().map(|x| *x.uuid()), Some(Uuid::from_u128(2)));
+        assert!(tag_3.items.contains_key(&Uuid::from_u128(10)));
+        assert_eq!(loaded.items.keys().copied().collect::<Vec<_>>(), [11, 10, 12].map(Uuid::from_u128));
+        let item = &loaded.items[&Uuid::from_u128(11)];
+        assert_eq!(item.tag_uuids().copied().collect::<Vec<_>>(), [5, 2].map(Uuid::from_u128));
+    }
+
+    #[test]
+    fn read_rejects_dangling() {
+        let mut dry = DryVolume::from_editor(Uuid::from_u128(99), "volume".to_string(), &editor()).unwrap();
+        dry.assignments.push(Assignment { item: Uuid::from_u128(13), tag: Uuid::from_u128(2) });
+        let json = serde_json::to_vec(&dry).unwrap();
+        assert!(matches!(read(&json), Err(VolumeFileError::Dangling { report }) if report.dangling.len() == 1));
+    }
+}
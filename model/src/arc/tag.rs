@@ -1,38 +1,74 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak, OnceLock};
 use std::fmt::Debug;
 use derive_builder::Builder;
 
 use super::prelude::{Uuid, IndexMap, CoreTag, ProtoTag, ModelTag, Item};
 
+/// A tag in a volume's tree, owned by its parent through `children`.
+///
+/// `parent` is a weak back-link, set once after the parent is built.
 #[derive(Clone, Debug, Builder)]
 pub struct Tag<TD: Debug, ID: Debug> {
     pub data: TD,
     pub proto: Arc<dyn ProtoTag + Send + Sync>,
-    #[builder(setter(into, strip_option), default)]
-    pub parent: Option<Arc<Tag<TD, ID>>>,
+    #[builder(setter(custom), default)]
+    pub parent: OnceLock<Weak<Tag<TD, ID>>>,
     #[builder(default)]
     pub children: IndexMap<Uuid, Arc<Tag<TD, ID>>>,
     #[builder(default)]
     pub items: IndexMap<Uuid, Arc<Item<TD, ID>>>,
 }
 
+impl<TD: Debug, ID: Debug> TagBuilder<TD, ID> {
+    /// Link to the parent tag, kept as a weak back-link.
+    pub fn parent(&mut self, parent: &Arc<Tag<TD, ID>>) -> &mut Self {
+        self.parent = Some(OnceLock::from(Arc::downgrade(parent)));
+        self
+    }
+}
+
+impl<TD: Debug, ID: Debug> Tag<TD, ID> {
+    /// The value of the proto tag if it is a `ValTag<V>`.
+    pub fn val<V: Debug + 'static>(&self) -> Option<&V> {
+        self.proto.val::<V>()
+    }
+
+    /// The parent tag, None for the root or once the tree is dropped.
+    pub fn parent_tag(&self) -> Option<Arc<Tag<TD, ID>>> {
+        self.parent.get().and_then(Weak::upgrade)
+    }
+}
+
+impl<TD: Debug, ID: Debug> Drop for Tag<TD, ID> {
+    // children are dropped in a loop instead of recursively, so deep trees
+    // can't overflow the stack
+    fn drop(&mut self) {
+        let mut stack: Vec<Arc<Tag<TD, ID>>> = std::mem::take(&mut self.children).into_values().collect();
+        while let Some(child) = stack.pop() {
+            if let Some(mut child) = Arc::into_inner(child) {
+                stack.extend(std::mem::take(&mut child.children).into_values());
+            }
+        }
+    }
+}
+
 impl<TD: Debug, ID: Debug> CoreTag for Tag<TD, ID> {
     fn uuid(&self) -> &Uuid {
         self.proto.uuid()
     }
 
     fn has_parent(&self) -> bool {
-        self.parent.is_some()
+        self.parent.get().is_some()
     }
 }
 
-impl<TD: Debug, ID: Debug> ProtoTag for Tag<TD, ID> {
+impl<TD: Debug + 'static, ID: Debug + 'static> ProtoTag for Tag<TD, ID> {
     fn parent(&self) -> Option<&Uuid> {
-        self.parent.as_ref().map(|x| { x.uuid() })
+        self.proto.parent()
     }
 }
 
-impl<TD: Debug, ID: Debug> ModelTag for Tag<TD, ID> {
+impl<TD: Debug + 'static, ID: Debug + 'static> ModelTag for Tag<TD, ID> {
     type Data = TD;
     type Item = Item<TD, ID>;
 
@@ -70,3 +106,58 @@ impl<TD: Debug, ID: Debug> ModelTag for Tag<TD, ID> {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arc::editor::VolumeEditor;
+    use crate::arc::item::ItemBuilder;
+    use crate::testing::{tag, assign, build};
+
+    #[test]
+    fn builders_link_back() {
+        let root: Arc<Tag<(), ()>> = Arc::new(TagBuilder::default().data(()).proto(tag(1, None)).build().unwrap());
+        let child = Arc::new(TagBuilder::default().data(()).proto(tag(2, Some(1))).parent(&root).build().unwrap());
+        assert_eq!(child.parent_tag().map(|x| *x.uuid()), Some(Uuid::from_u128(1)));
+        assert!(child.has_parent() && !root.has_parent());
+        let item = ItemBuilder::default().uuid(Uuid::from_u128(10)).data(()).tags([&root, &child]).build().unwrap();
+        assert_eq!(item.tag_uuids().copied().collect::<Vec<_>>(), [1, 2].map(Uuid::from_u128));
+        assert!(item.get_tag(&Uuid::from_u128(2)).is_some());
+        drop(child);
+        assert!(item.get_tag(&Uuid::from_u128(2)).is_none());
+    }
+
+    #[test]
+    fn dropping_volume_frees_everything() {
+        let mut editor = VolumeEditor::<(), ()>::new(tag(0, None), ());
+        for uuid in 1..100u128 {
+            editor.add_tag(tag(uuid, Some(uuid / 2)), ()).unwrap();
+            assign(&mut editor, &[(1000 + uuid, uuid), (1000 + uuid, uuid / 2)], ());
+        }
+        let volume = Arc::new(build(editor, 0, ()));
+        let weak_volume = Arc::downgrade(&volume);
+        let item = volume.items[&Uuid::from_u128(1006)].clone();
+        let weak_tag = item.get_tag(&Uuid::from_u128(3)).map(|x| Arc::downgrade(&x)).unwrap();
+        let weak_item = Arc::downgrade(&item);
+        assert!(weak_tag.upgrade().and_then(|x| x.parent_tag()).is_some());
+        let tags: Vec<Weak<Tag<(), ()>>> = volume.root.children.values().map(Arc::downgrade).collect();
+        drop(item);
+        drop(volume);
+        assert!(weak_volume.upgrade().is_none());
+        assert!(weak_tag.upgrade().is_none());
+        assert!(weak_item.upgrade().is_none());
+        assert!(tags.iter().all(|x| x.upgrade().is_none()));
+    }
+
+    #[test]
+    fn deep_chain_drops_without_overflow() {
+        let mut editor = VolumeEditor::<(), ()>::new(tag(0, None), ());
+        for uuid in 1..100_000u128 {
+            editor.add_tag(tag(uuid, Some(uuid - 1)), ()).unwrap();
+        }
+        let (root, _) = editor.build_parts().unwrap();
+        let weak = Arc::downgrade(&root);
+        drop(root);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -7,6 +7,11 @@ use async_trait::async_trait;
 
 use super::prelude::{Uuid, Hash, IndexMap, LoadResult, Item, Tag,ModelVolume};
 
+/// A volume as a graph of `Arc`s that only own downwards: the volume owns its
+/// root and items, tags own their children and items, while `Tag.parent` and
+/// `Item.tags` are weak, so dropping the last handle frees all of it.
+///
+/// Use `VolumeEditor` to get all links set up consistently.
 #[derive(Clone, Debug, Builder)]
 pub struct Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
     where
@@ -27,6 +32,28 @@ pub struct Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
     async_loader: AsyncLoader,
 }
 
+impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
+    where
+        TD: Debug,
+        ID: Debug,
+        VD: Debug,
+        Loader: Fn(&Hash) -> LoadResult<Body>,
+        AsyncLoader: Fn(&Hash) -> TF,
+        TF: Future<Output = LoadResult<Body>>
+{
+    // without the builder, which needs every generic to be `Clone`
+    pub(crate) fn new(
+        uuid: Uuid,
+        data: VD,
+        root: Arc<Tag<TD, ID>>,
+        items: IndexMap<Uuid, Arc<Item<TD, ID>>>,
+        loader: Loader,
+        async_loader: AsyncLoader,
+    ) -> Self {
+        Self { uuid, data, root, items, loader, async_loader }
+    }
+}
+
 #[async_trait]
 impl<TD, ID, VD, Body, Loader, AsyncLoader, TF> ModelVolume for Volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>
     where
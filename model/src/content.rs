@@ -0,0 +1,339 @@
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, Hash, IndexMap, IndexSet, SerdeTag, SerdeTags};
+
+use tag_proto::serde::registry::get_registration;
+
+const CONTEXT: &str = "edger-dev/tag content hash v1";
+
+#[derive(Debug, Snafu)]
+pub enum ContentError {
+    #[snafu(display("Value failed: `{}` -> {}", uuid, info))]
+    ValueFailed { uuid: Uuid, info: String },
+    #[snafu(display("Unknown type: {}", info))]
+    UnknownType { info: String },
+    #[snafu(display("Not supported: `{}`", uuid))]
+    NotSupported { uuid: Uuid },
+}
+
+pub type ContentResult<T> = std::result::Result<T, ContentError>;
+
+fn canonical_value(tag: &dyn SerdeTag) -> ContentResult<serde_json::Value> {
+    let mut value = serde_json::to_value(tag.payload())
+        .map(sort_keys)
+        .map_err(|x| ContentError::ValueFailed { uuid: *tag.uuid(), info: x.to_string() })?;
+    if let Some(("ValTag", [value_type])) = type_args(&tag.tag_type_name()).as_ref().map(|(x, args)| (*x, args.as_slice())) {
+        sort_sets(&mut value, value_type);
+    }
+    Ok(value)
+}
+
+// name and top level arguments of a type name, e.g. `Map` with `String` and `Set<u32>`
+fn type_args(name: &str) -> Option<(&str, Vec<&str>)> {
+    let (head, rest) = name.split_once('<')?;
+    let inner = rest.strip_suffix('>')?;
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[start..index].trim());
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    args.push(inner[start..].trim());
+    Some((head, args))
+}
+
+// members of `Set` values sorted by their JSON, as a `HashSet` iterates in random order
+fn sort_sets(value: &mut serde_json::Value, value_type: &str) {
+    let Some((head, args)) = type_args(value_type) else {
+        return;
+    };
+    match (head, args.as_slice(), value) {
+        ("Set", [member], serde_json::Value::Array(values)) => {
+            values.iter_mut().for_each(|x| sort_sets(x, member));
+            values.sort_by_cached_key(|x| x.to_string());
+        },
+        ("Vec" | "IndexSet", [member], serde_json::Value::Array(values)) => {
+            values.iter_mut().for_each(|x| sort_sets(x, member));
+        },
+        ("Map" | "IndexMap", [_, member], serde_json::Value::Object(map)) => {
+            map.values_mut().for_each(|x| sort_sets(x, member));
+        },
+        ("Option", [member], value) => sort_sets(value, member),
+        _ => {},
+    }
+}
+
+// objects rebuilt in key order, whether or not serde_json preserves insertion order
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(entries.into_iter().map(|(k, v)| (k, sort_keys(v))).collect())
+        },
+        serde_json::Value::Array(values) => serde_json::Value::Array(values.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+/// Hash of what a tag holds: its type name, parent and value, not its own uuid or metadata.
+///
+/// Values are hashed as JSON with sorted object keys and, for `ValTag` values,
+/// sorted set members, so the hash doesn't depend on field or iteration order.
+/// Sets inside other tag types are hashed in their iteration order.
+pub fn content_hash(tag: &dyn SerdeTag) -> ContentResult<Hash> {
+    content_hash_with_parent(tag, tag.parent())
+}
+
+/// Hash of a tag as if it was under `parent`.
+pub fn content_hash_with_parent(tag: &dyn SerdeTag, parent: Option<&Uuid>) -> ContentResult<Hash> {
+    let value = canonical_value(tag)?;
+    let mut hasher = blake3::Hasher::new_derive_key(CONTEXT);
+    let tag_type = tag.tag_type_name();
+    hasher.update(&(tag_type.len() as u64).to_le_bytes());
+    hasher.update(tag_type.as_bytes());
+    match parent {
+        Some(parent) => {
+            hasher.update(&[1]);
+            hasher.update(parent.as_bytes());
+        },
+        None => {
+            hasher.update(&[0]);
+        },
+    }
+    hasher.update(value.to_string().as_bytes());
+    Ok(hasher.finalize())
+}
+
+/// Uuid (version 8) made of the first bytes of a content hash.
+pub fn content_uuid(hash: &Hash) -> Uuid {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash.as_bytes()[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
+}
+
+/// Copy of a tag with another uuid and parent, going through its payload.
+///
+/// Fails with `NotSupported` for types with the uuid or parent in their payload.
+pub fn rebuild_tag(tag: &dyn SerdeTag, uuid: Uuid, parent: Option<Uuid>) -> ContentResult<Box<dyn SerdeTag>> {
+    let registration = get_registration(&tag.tag_type_name())
+        .map_err(|info| ContentError::UnknownType { info })?;
+    let mut erased = <dyn erased_serde::Deserializer>::erase(canonical_value(tag)?);
+    let mut rebuilt = (registration.from_payload)(uuid, parent, &mut erased)
+        .map_err(|x| ContentError::ValueFailed { uuid: *tag.uuid(), info: x.to_string() })?;
+    ensure!(rebuilt.uuid() == &uuid && rebuilt.parent() == parent.as_ref(), NotSupportedSnafu { uuid: *tag.uuid() });
+    if let (Some(meta), Some(rebuilt_meta)) = (tag.meta(), rebuilt.meta_mut()) {
+        rebuilt_meta.clone_from(meta);
+    }
+    Ok(rebuilt)
+}
+
+/// Tags keyed by content, so tags holding the same thing under the same
+/// parent end up as one, no matter which uuids their sources gave them.
+///
+/// Tags are added parents first and hashed under the content uuid of their
+/// parent once it is indexed, so equal trees match as a whole.
+#[derive(Debug, Clone, Default)]
+pub struct ContentIndex {
+    by_hash: IndexMap<Hash, Uuid>,
+    hashes: IndexMap<Uuid, Hash>,
+    remap: IndexMap<Uuid, Uuid>,
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_hash.is_empty()
+    }
+
+    pub fn hash_of(&self, uuid: &Uuid) -> Option<&Hash> {
+        self.hashes.get(self.resolve(uuid))
+    }
+
+    pub fn get(&self, hash: &Hash) -> Option<&Uuid> {
+        self.by_hash.get(hash)
+    }
+
+    /// The uuid a merged tag was replaced by, or `uuid` itself.
+    pub fn resolve<'a>(&'a self, uuid: &'a Uuid) -> &'a Uuid {
+        self.remap.get(uuid).unwrap_or(uuid)
+    }
+
+    /// Uuids of merged tags and the ones they were merged into.
+    pub fn remap(&self) -> &IndexMap<Uuid, Uuid> {
+        &self.remap
+    }
+
+    /// Add a tag, returning the uuid of an earlier tag with the same content if there is one.
+    pub fn insert(&mut self, tag: &dyn SerdeTag) -> ContentResult<Option<Uuid>> {
+        let parent = tag.parent().map(|x| self.hash_of(x).map(content_uuid).unwrap_or(*x));
+        let hash = content_hash_with_parent(tag, parent.as_ref())?;
+        match self.by_hash.get(&hash) {
+            Some(existing) if existing != tag.uuid() => {
+                let existing = *existing;
+                self.remap.insert(*tag.uuid(), existing);
+                Ok(Some(existing))
+            },
+            Some(_) => Ok(None),
+            None => {
+                self.by_hash.insert(hash, *tag.uuid());
+                self.hashes.insert(*tag.uuid(), hash);
+                Ok(None)
+            },
+        }
+    }
+
+    /// Hash of all contents regardless of order or uuids, equal for sets of
+    /// trees holding the same things.
+    pub fn digest(&self) -> Hash {
+        let mut hashes: Vec<&Hash> = self.by_hash.keys().collect();
+        hashes.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        let mut hasher = blake3::Hasher::new_derive_key(CONTEXT);
+        for hash in hashes {
+            hasher.update(hash.as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    /// Merge tags of any number of sources, keeping the first of each content
+    /// and the first of each uuid.
+    ///
+    /// Kept tags under a merged parent are rebuilt under the tag it was merged into.
+    pub fn dedup<'a, I>(&mut self, tags: I) -> ContentResult<SerdeTags>
+        where I: IntoIterator<Item = &'a dyn SerdeTag>
+    {
+        let mut result = Vec::new();
+        for tag in parents_first(tags) {
+            if self.hashes.contains_key(tag.uuid()) || self.insert(tag)?.is_some() {
+                continue;
+            }
+            let parent = tag.parent().map(|x| *self.resolve(x));
+            if parent.as_ref() == tag.parent() {
+                result.push(tag.clone_tag());
+            } else {
+                result.push(rebuild_tag(tag, *tag.uuid(), parent)?);
+            }
+        }
+        Ok(SerdeTags(result))
+    }
+}
+
+/// Order tags so parents come before their children, keeping the order otherwise.
+fn parents_first<'a, I>(tags: I) -> Vec<&'a dyn SerdeTag>
+    where I: IntoIterator<Item = &'a dyn SerdeTag>
+{
+    let tags: Vec<&dyn SerdeTag> = tags.into_iter().collect();
+    let uuids: IndexSet<&Uuid> = tags.iter().map(|x| x.uuid()).collect();
+    let mut children: IndexMap<&Uuid, Vec<usize>> = IndexMap::new();
+    let mut stack = Vec::new();
+    for (index, tag) in tags.iter().enumerate() {
+        match tag.parent().filter(|x| uuids.contains(x)) {
+            Some(parent) => children.entry(parent).or_default().push(index),
+            None => stack.push(index),
+        }
+    }
+    stack.reverse();
+    let mut visited = vec![false; tags.len()];
+    let mut result = Vec::with_capacity(tags.len());
+    while let Some(index) = stack.pop() {
+        if std::mem::replace(&mut visited[index], true) {
+            continue;
+        }
+        result.push(tags[index]);
+        if let Some(indexes) = children.get(tags[index].uuid()) {
+            stack.extend(indexes.iter().rev());
+        }
+    }
+    // tags in parent cycles, in their original order
+    result.extend(tags.iter().enumerate().filter(|(index, _)| !visited[*index]).map(|(_, x)| *x));
+    result
+}
+
+/// Copies of tags identified by their content, with parents remapped to the
+/// new uuids, plus the old uuids mapped to the new ones.
+///
+/// Tags with the same content end up with the same uuid and are kept once.
+pub fn content_addressed<'a, I>(tags: I) -> ContentResult<(SerdeTags, IndexMap<Uuid, Uuid>)>
+    where I: IntoIterator<Item = &'a dyn SerdeTag>
+{
+    let mut uuids = IndexMap::new();
+    let mut result: IndexMap<Uuid, Box<dyn SerdeTag>> = IndexMap::new();
+    for tag in parents_first(tags) {
+        let parent = tag.parent().map(|x| *uuids.get(x).unwrap_or(x));
+        let uuid = content_uuid(&content_hash_with_parent(tag, parent.as_ref())?);
+        uuids.insert(*tag.uuid(), uuid);
+        if !result.contains_key(&uuid) {
+            result.insert(uuid, rebuild_tag(tag, uuid, parent)?);
+        }
+    }
+    Ok((SerdeTags(result.into_values().collect()), uuids))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::prelude::{IndexMap, ValTag, Value, DynTag, TagMeta};
+
+    use super::*;
+
+    fn map_tag(uuid: u128, keys: &[&str]) -> DynTag {
+        let val: IndexMap<String, Value> = keys.iter()
+            .map(|x| (x.to_string(), Value::String(x.to_string())))
+            .collect();
+        ValTag { uuid: Uuid::from_u128(uuid), parent: Some(Uuid::from_u128(9)), val: Value::Map(val), meta: TagMeta::default() }
+    }
+
+    #[test]
+    fn hash_ignores_field_order_and_uuid() {
+        let a = content_hash(&map_tag(1, &["b", "a", "c"])).unwrap();
+        let b = content_hash(&map_tag(2, &["c", "b", "a"])).unwrap();
+        assert_eq!(a, b);
+        let c = content_hash(&map_tag(1, &["a", "b"])).unwrap();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn hash_ignores_set_and_map_order() {
+        type Sets = HashMap<String, HashSet<u32>>;
+        let tag = |keys: &[&str], members: &[u32]| {
+            let val: Sets = keys.iter().map(|x| (x.to_string(), members.iter().copied().collect())).collect();
+            ValTag { uuid: Uuid::from_u128(1), parent: None, val: Some(val), meta: TagMeta::default() }
+        };
+        let members: Vec<u32> = (0..64).collect();
+        let reversed: Vec<u32> = members.iter().rev().copied().collect();
+        let a = content_hash(&tag(&["a", "b", "c"], &members)).unwrap();
+        let b = content_hash(&tag(&["c", "b", "a"], &reversed)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, content_hash(&tag(&["a", "b", "c"], &members[1..])).unwrap());
+    }
+
+    #[test]
+    fn type_args_split_at_top_level() {
+        assert_eq!(type_args("Map<String, Map<u32, Set<u64>>>"), Some(("Map", vec!["String", "Map<u32, Set<u64>>"])));
+        assert_eq!(type_args("String"), None);
+    }
+
+    #[test]
+    fn nested_keys_are_sorted() {
+        let value = serde_json::json!({"b": [{"y": 1, "x": 2}], "a": {"d": 3, "c": 4}});
+        assert_eq!(sort_keys(value).to_string(), r#"{"a":{"c":4,"d":3},"b":[{"x":2,"y":1}]}"#);
+    }
+}
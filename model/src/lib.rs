@@ -1,14 +1,32 @@
 pub use blake3;
 
+#[cfg(feature = "arrow")]
+pub use arrow;
+
+#[cfg(feature = "parquet")]
+pub use parquet;
+
 pub use tag_proto;
 pub use tag_proto::tag_core;
 
 pub mod item;
 pub mod tag;
 pub mod volume;
+pub mod spatial;
+pub mod search;
+pub mod records;
+
+#[cfg(feature = "arrow")]
+pub mod table;
+
+#[cfg(feature = "content")]
+pub mod content;
 
 pub mod arc;
 
+#[cfg(test)]
+mod testing;
+
 pub mod prelude {
     #[doc(hidden)]
     pub use blake3::Hash;
@@ -24,4 +42,21 @@ pub mod prelude {
 
     #[doc(hidden)]
     pub use crate::volume::{Volume, LoadError, LoadResult};
+
+    #[doc(hidden)]
+    pub use crate::spatial::SpatialIndex;
+
+    #[doc(hidden)]
+    pub use crate::search::TagSearch;
+
+    #[doc(hidden)]
+    pub use crate::records::{ItemRecord, Assignment, item_records};
+
+    #[cfg(feature = "arrow")]
+    #[doc(hidden)]
+    pub use crate::table::{TagTables, TableError, TableResult};
+
+    #[cfg(feature = "content")]
+    #[doc(hidden)]
+    pub use crate::content::{ContentIndex, ContentError, content_hash, content_uuid, content_addressed};
 }
\ No newline at end of file
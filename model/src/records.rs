@@ -0,0 +1,43 @@
+use std::cell::RefCell;
+
+use crate::prelude::{Uuid, Hash, CoreTag, Item, Volume};
+
+/// An item without its data, as stored outside of a volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemRecord {
+    pub uuid: Uuid,
+    pub body: Option<Hash>,
+}
+
+/// A tag assigned to an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Assignment {
+    pub item: Uuid,
+    pub tag: Uuid,
+}
+
+/// Records of the items of a volume and their tags, in volume order.
+pub fn item_records<V>(volume: &V) -> (Vec<ItemRecord>, Vec<Assignment>)
+    where
+        V: Volume,
+        V::Item: Item,
+{
+    let items = RefCell::new(Vec::with_capacity(volume.items_count()));
+    let assignments = RefCell::new(Vec::new());
+    volume.each_item(&|item: &V::Item| {
+        items.borrow_mut().push(ItemRecord {
+            uuid: *item.uuid(),
+            body: item.body().copied(),
+        });
+        item.each_tag(&|tag| {
+            assignments.borrow_mut().push(Assignment {
+                item: *item.uuid(),
+                tag: *tag.uuid(),
+            });
+            false
+        });
+        false
+    });
+    (items.into_inner(), assignments.into_inner())
+}
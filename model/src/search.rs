@@ -0,0 +1,165 @@
+use std::sync::Arc;
+use std::fmt::Debug;
+
+use crate::prelude::{Uuid, IndexMap, IndexSet, Label, Labels, CoreTag};
+use crate::arc::tag::Tag as ArcTag;
+use crate::arc::item::Item as ArcItem;
+
+use tag_proto::names::split_path;
+
+/// Tags of an arc tag tree indexed by their labels, so lookups by any alias
+/// or localized name end up at the same tag.
+#[derive(Debug, Clone)]
+pub struct TagSearch<TD: Debug, ID: Debug> {
+    root: Arc<ArcTag<TD, ID>>,
+    tags: IndexMap<Uuid, Arc<ArcTag<TD, ID>>>,
+    labels: Labels,
+}
+
+impl<TD: Debug + 'static, ID: Debug + 'static> TagSearch<TD, ID> {
+    pub fn new(root: Arc<ArcTag<TD, ID>>, labels: Labels) -> Self {
+        let mut tags = IndexMap::new();
+        let mut stack = vec![root.clone()];
+        while let Some(tag) = stack.pop() {
+            if tags.contains_key(tag.uuid()) {
+                continue;
+            }
+            stack.extend(tag.children.values().rev().cloned());
+            tags.insert(*tag.uuid(), tag);
+        }
+        Self { root, tags, labels }
+    }
+
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    pub fn set_label(&mut self, uuid: Uuid, label: Label) -> Option<Label> {
+        self.labels.set(uuid, label)
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&Arc<ArcTag<TD, ID>>> {
+        self.tags.get(uuid)
+    }
+
+    /// Tags with `term` as label, alias or localized name.
+    pub fn find(&self, term: &str) -> Vec<&Arc<ArcTag<TD, ID>>> {
+        self.labels.lookup_all(term)
+            .filter_map(|x| self.tags.get(x))
+            .collect()
+    }
+
+    /// Tags with any name starting with `prefix`, best matches first, see `Labels::search`.
+    pub fn search(&self, prefix: &str) -> Vec<&Arc<ArcTag<TD, ID>>> {
+        self.labels.search(prefix).iter()
+            .filter_map(|x| self.tags.get(x))
+            .collect()
+    }
+
+    /// Follow a path of labels from the root, any name of a child matches a segment.
+    pub fn resolve(&self, path: &str) -> Option<&Arc<ArcTag<TD, ID>>> {
+        let mut current = &self.root;
+        for segment in split_path(path) {
+            let mut found = self.labels.lookup_all(segment)
+                .filter_map(|x| current.children.get(x));
+            let child = found.next()?;
+            if found.next().is_some() {
+                return None;
+            }
+            current = child;
+        }
+        Some(current)
+    }
+
+    /// Items on tags matching `term` or on any of their descendants.
+    pub fn items(&self, term: &str) -> IndexMap<Uuid, Arc<ArcItem<TD, ID>>> {
+        let mut items = IndexMap::new();
+        let mut visited = IndexSet::new();
+        let mut stack: Vec<&Arc<ArcTag<TD, ID>>> = self.find(term);
+        while let Some(tag) = stack.pop() {
+            if !visited.insert(*tag.uuid()) {
+                continue;
+            }
+            items.extend(tag.items.iter().map(|(uuid, item)| (*uuid, item.clone())));
+            stack.extend(tag.children.values());
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arc::prelude::VolumeEditor;
+    use crate::testing::{tag, assign, build, TestVolume};
+
+    fn volume() -> TestVolume<(), (), ()> {
+        let mut editor = VolumeEditor::new(tag(1, None), ());
+        for (uuid, parent) in [(2, 1), (3, 2), (4, 1), (5, 4), (6, 4)] {
+            editor.add_tag(tag(uuid, Some(parent)), ()).unwrap();
+        }
+        assign(&mut editor, &[(10, 3), (11, 4), (12, 2)], ());
+        build(editor, 99, ())
+    }
+
+    fn search(volume: &TestVolume<(), (), ()>) -> TagSearch<(), ()> {
+        let mut labels = Labels::new();
+        labels.set(Uuid::from_u128(1), Label::new("Music"));
+        labels.set(Uuid::from_u128(2), Label::new("Jazz").with_alias("Jazz Music").with_localized("fr", "Jazz (musique)"));
+        labels.set(Uuid::from_u128(3), Label::new("Bebop").with_alias("Bop"));
+        labels.set(Uuid::from_u128(4), Label::new("Rock"));
+        labels.set(Uuid::from_u128(5), Label::new("Jazz Rock").with_alias("Fusion"));
+        labels.set(Uuid::from_u128(6), Label::new("Fusion"));
+        TagSearch::new(volume.root.clone(), labels)
+    }
+
+    fn uuids(tags: Vec<&Arc<ArcTag<(), ()>>>) -> Vec<u128> {
+        tags.into_iter().map(|x| x.uuid().as_u128()).collect()
+    }
+
+    #[test]
+    fn find_by_any_name() {
+        let volume = volume();
+        let search = search(&volume);
+        assert_eq!(uuids(search.find("JAZZ MUSIC")), [2]);
+        assert_eq!(uuids(search.find("jazz (musique)")), [2]);
+        assert_eq!(uuids(search.find("fusion")), [5, 6]);
+        assert!(search.find("blues").is_empty());
+    }
+
+    #[test]
+    fn search_ranks_prefix_matches() {
+        let volume = volume();
+        let search = search(&volume);
+        assert_eq!(uuids(search.search("jazz")), [2, 5]);
+        assert_eq!(uuids(search.search("Jazz R")), [5]);
+        assert_eq!(uuids(search.search("b")), [3]);
+        assert_eq!(uuids(search.search("fu")), [5, 6]);
+        assert_eq!(search.search("").len(), 6);
+    }
+
+    #[test]
+    fn resolve_label_paths() {
+        let volume = volume();
+        let search = search(&volume);
+        let resolve = |path| search.resolve(path).map(|x| x.uuid().as_u128());
+        assert_eq!(resolve("jazz/bop"), Some(3));
+        assert_eq!(resolve("Jazz (musique)/Bebop"), Some(3));
+        assert_eq!(resolve("rock/jazz rock"), Some(5));
+        assert_eq!(resolve(""), Some(1));
+        assert_eq!(resolve("bebop"), None);
+        // both children of rock are called fusion
+        assert_eq!(resolve("rock/fusion"), None);
+    }
+
+    #[test]
+    fn items_below_matches() {
+        let volume = volume();
+        let search = search(&volume);
+        let mut items: Vec<u128> = search.items("jazz").keys().map(|x| x.as_u128()).collect();
+        items.sort();
+        assert_eq!(items, [10, 12]);
+        assert_eq!(search.items("rock").len(), 1);
+        assert!(search.items("blues").is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::fmt::Debug;
+
+use crate::prelude::{Uuid, IndexMap, IndexSet, Point, BBox, Geometry, Spatial, SpatialTag};
+use crate::arc::item::Item as ArcItem;
+
+type Cell = (i32, i32);
+
+/// Grid index from item uuids to the geometries of their tags.
+///
+/// A geometry is listed in every cell its bounding box touches, so keep
+/// `cell_size` (in degrees) well above the size of typical polygons.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    cell_size: f64,
+    items: IndexMap<Uuid, Vec<Geometry>>,
+    cells: IndexMap<Cell, IndexSet<Uuid>>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            items: IndexMap::new(),
+            cells: IndexMap::new(),
+        }
+    }
+
+    /// Index the geometries of all tags on the items, see `SpatialTag` for which tags count.
+    pub fn from_items<'a, TD, ID, I>(items: I) -> Self
+        where
+            TD: Debug + 'static,
+            ID: Debug + 'static,
+            I: IntoIterator<Item = &'a Arc<ArcItem<TD, ID>>>,
+    {
+        let mut index = Self::default();
+        for item in items {
+            index.insert_item(item);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn geometries(&self, item: &Uuid) -> &[Geometry] {
+        self.items.get(item).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    fn cell_range(&self, bbox: &BBox) -> (Cell, Cell) {
+        let cell = |x: f64| (x / self.cell_size).floor() as i32;
+        ((cell(bbox.min.lat), cell(bbox.min.lon)), (cell(bbox.max.lat), cell(bbox.max.lon)))
+    }
+
+    fn cells_of(&self, bbox: &BBox) -> impl Iterator<Item = Cell> {
+        let ((min_lat, min_lon), (max_lat, max_lon)) = self.cell_range(bbox);
+        (min_lat..=max_lat).flat_map(move |lat| (min_lon..=max_lon).map(move |lon| (lat, lon)))
+    }
+
+    pub fn insert(&mut self, item: Uuid, geometry: Geometry) {
+        if let Some(bbox) = geometry.bbox() {
+            for cell in self.cells_of(&bbox).collect::<Vec<_>>() {
+                self.cells.entry(cell).or_default().insert(item);
+            }
+        }
+        self.items.entry(item).or_default().push(geometry);
+    }
+
+    /// Replace whatever was indexed for the item with the geometries of its tags.
+    pub fn insert_item<TD: Debug + 'static, ID: Debug + 'static>(&mut self, item: &ArcItem<TD, ID>) {
+        self.remove(&item.uuid);
+        for tag in item.tags() {
+            if let Some(geometry) = tag.proto.geometry() {
+                self.insert(item.uuid, geometry);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, item: &Uuid) -> bool {
+        let Some(geometries) = self.items.shift_remove(item) else {
+            return false;
+        };
+        for bbox in geometries.iter().filter_map(|x| x.bbox()) {
+            for cell in self.cells_of(&bbox).collect::<Vec<_>>() {
+                if let Some(uuids) = self.cells.get_mut(&cell) {
+                    uuids.shift_remove(item);
+                    if uuids.is_empty() {
+                        self.cells.shift_remove(&cell);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn candidates(&self, bbox: &BBox) -> IndexSet<Uuid> {
+        let ((min_lat, min_lon), (max_lat, max_lon)) = self.cell_range(bbox);
+        // inverted boxes span no cells
+        let span = |min: i32, max: i32| usize::try_from(i64::from(max) - i64::from(min) + 1).unwrap_or(0);
+        let cell_count = span(min_lat, max_lat).saturating_mul(span(min_lon, max_lon));
+        // scanning is cheaper than probing lots of empty cells for big boxes
+        if cell_count > self.cells.len() {
+            return self.cells.iter()
+                .filter(|((lat, lon), _)| (min_lat..=max_lat).contains(lat) && (min_lon..=max_lon).contains(lon))
+                .flat_map(|(_, uuids)| uuids.iter().copied())
+                .collect();
+        }
+        self.cells_of(bbox)
+            .filter_map(|x| self.cells.get(&x))
+            .flat_map(|x| x.iter().copied())
+            .collect()
+    }
+
+    /// Items with any geometry touching `bbox`.
+    pub fn within_bbox(&self, bbox: &BBox) -> Vec<Uuid> {
+        self.candidates(bbox).into_iter()
+            .filter(|x| self.geometries(x).iter().any(|x| x.intersects_bbox(bbox)))
+            .collect()
+    }
+
+    /// Items with any geometry within `radius` meters of `center`, nearest first.
+    pub fn within_radius(&self, center: &Point, radius: f64) -> Vec<Uuid> {
+        let candidates: IndexSet<Uuid> = BBox::around(center, radius).iter()
+            .flat_map(|x| self.candidates(x))
+            .collect();
+        let mut found: Vec<(f64, Uuid)> = candidates.into_iter()
+            .filter(|x| self.geometries(x).iter().any(|x| x.within_radius(center, radius)))
+            .map(|x| (self.distance(&x, center), x))
+            .collect();
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        found.into_iter().map(|(_, x)| x).collect()
+    }
+
+    // distance to the nearest point, or the polygon bbox center, for ordering results
+    fn distance(&self, item: &Uuid, center: &Point) -> f64 {
+        self.geometries(item).iter()
+            .map(|x| match x {
+                Geometry::Point(point) => point.distance_to(center),
+                Geometry::Polygon(polygon) if polygon.contains(center) => 0.0,
+                Geometry::Polygon(polygon) => polygon.bbox()
+                    .map(|x| Point::new((x.min.lat + x.max.lat) / 2.0, (x.min.lon + x.max.lon) / 2.0).distance_to(center))
+                    .unwrap_or(f64::INFINITY),
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radius_across_antimeridian() {
+        let mut index = SpatialIndex::default();
+        index.insert(Uuid::from_u128(1), Geometry::Point(Point::new(0.0, -179.9)));
+        index.insert(Uuid::from_u128(2), Geometry::Point(Point::new(0.0, 170.0)));
+        assert_eq!(index.within_radius(&Point::new(0.0, 179.9), 50_000.0), vec![Uuid::from_u128(1)]);
+    }
+
+    #[test]
+    fn inverted_bbox_is_empty() {
+        let mut index = SpatialIndex::default();
+        index.insert(Uuid::from_u128(1), Geometry::Point(Point::new(0.0, 0.0)));
+        let inverted = BBox::new(Point::new(10.0, 10.0), Point::new(-10.0, -10.0));
+        assert!(index.within_bbox(&inverted).is_empty());
+        let huge = BBox::new(Point::new(-1e12, -1e12), Point::new(1e12, 1e12));
+        assert_eq!(index.within_bbox(&huge), vec![Uuid::from_u128(1)]);
+    }
+}
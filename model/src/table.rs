@@ -0,0 +1,475 @@
+use std::sync::Arc;
+use std::fmt::Debug;
+use std::future::Future;
+
+use snafu::prelude::*;
+
+use arrow::array::{Array, ArrayRef, StringArray, TimestampMillisecondArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+
+use crate::prelude::{Uuid, Hash, IndexMap, TagMeta, SerdeTag, SerdeTags, Item, LoadResult};
+use crate::records::{ItemRecord, Assignment};
+use crate::arc::volume::Volume as ArcVolume;
+use crate::arc::hydrate::DryVolume;
+
+use tag_proto::serde::registry::{get_registration, to_serde_tag};
+
+#[derive(Debug, Snafu)]
+pub enum TableError {
+    #[snafu(display("Arrow failed: {}", source))]
+    ArrowFailed { source: ArrowError },
+    #[cfg(feature = "parquet")]
+    #[snafu(display("Parquet failed: {}", source))]
+    ParquetFailed { source: parquet::errors::ParquetError },
+    #[snafu(display("IO failed: {} -> {}", info, error))]
+    IoFailed { error: std::io::Error, info: String },
+    #[snafu(display("Missing column: `{}`", name))]
+    MissingColumn { name: String },
+    #[snafu(display("Missing value: `{}` [{}]", name, row))]
+    MissingValue { name: String, row: usize },
+    #[snafu(display("Invalid uuid: `{}`", value))]
+    InvalidUuid { value: String },
+    #[snafu(display("Invalid hash: `{}`", value))]
+    InvalidHash { value: String },
+    #[snafu(display("Unknown type: {}", info))]
+    UnknownType { info: String },
+    #[snafu(display("Value failed: `{}` -> {}", uuid, info))]
+    ValueFailed { uuid: Uuid, info: String },
+    #[snafu(display("Not serializable: `{}` -> {}", uuid, info))]
+    NotSerializable { uuid: Uuid, info: String },
+    #[snafu(display("Root not found"))]
+    RootNotFound,
+}
+
+pub type TableResult<T> = std::result::Result<T, TableError>;
+
+/// Tags, items and assignments of a volume as flat tables, one row per record.
+///
+/// Uuids and hashes are strings and tag values are JSON next to their type name,
+/// so the tables can be queried as they are, e.g. with DuckDB or Polars.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagTables {
+    pub tags: SerdeTags,
+    pub items: Vec<ItemRecord>,
+    pub assignments: Vec<Assignment>,
+}
+
+pub fn tags_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("uuid", DataType::Utf8, false),
+        Field::new("parent", DataType::Utf8, true),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, false),
+        Field::new("created", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+        Field::new("modified", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+        Field::new("author", DataType::Utf8, true),
+        Field::new("source", DataType::Utf8, true),
+    ])
+}
+
+pub fn items_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("uuid", DataType::Utf8, false),
+        Field::new("body", DataType::Utf8, true),
+    ])
+}
+
+pub fn assignments_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("item", DataType::Utf8, false),
+        Field::new("tag", DataType::Utf8, false),
+    ])
+}
+
+fn strings<I: IntoIterator<Item = Option<String>>>(values: I) -> ArrayRef {
+    Arc::new(values.into_iter().collect::<StringArray>())
+}
+
+fn timestamps<I: IntoIterator<Item = Option<u64>>>(values: I) -> ArrayRef {
+    Arc::new(values.into_iter().map(|x| x.map(|x| x as i64)).collect::<TimestampMillisecondArray>())
+}
+
+fn column(batch: &RecordBatch, name: &str, data_type: &DataType) -> TableResult<ArrayRef> {
+    let column = batch.column_by_name(name).context(MissingColumnSnafu { name })?;
+    cast(column, data_type).context(ArrowFailedSnafu)
+}
+
+fn string_column(batch: &RecordBatch, name: &str) -> TableResult<Vec<Option<String>>> {
+    let column = column(batch, name, &DataType::Utf8)?;
+    let array = column.as_any().downcast_ref::<StringArray>().context(MissingColumnSnafu { name })?;
+    Ok(array.iter().map(|x| x.map(|x| x.to_owned())).collect())
+}
+
+fn timestamp_column(batch: &RecordBatch, name: &str) -> TableResult<Vec<Option<u64>>> {
+    let column = column(batch, name, &DataType::Timestamp(TimeUnit::Millisecond, None))?;
+    let array = column.as_any().downcast_ref::<TimestampMillisecondArray>().context(MissingColumnSnafu { name })?;
+    Ok(array.iter().map(|x| x.map(|x| x as u64)).collect())
+}
+
+fn required(value: Option<String>, name: &str, row: usize) -> TableResult<String> {
+    value.context(MissingValueSnafu { name, row })
+}
+
+fn parse_uuid(value: &str) -> TableResult<Uuid> {
+    Uuid::parse_str(value).ok().context(InvalidUuidSnafu { value })
+}
+
+fn parse_hash(value: &str) -> TableResult<Hash> {
+    Hash::from_hex(value).ok().context(InvalidHashSnafu { value })
+}
+
+fn read_tag(uuid: Uuid, parent: Option<Uuid>, tag_type: &str, value: &str, meta: TagMeta) -> TableResult<Box<dyn SerdeTag>> {
+    let registration = get_registration(tag_type)
+        .map_err(|info| TableError::UnknownType { info })?;
+    let mut json = serde_json::Deserializer::from_str(value);
+    let mut erased = <dyn erased_serde::Deserializer>::erase(&mut json);
+    let mut tag = (registration.from_payload)(uuid, parent, &mut erased)
+        .map_err(|x| TableError::ValueFailed { uuid, info: x.to_string() })?;
+    json.end().map_err(|x| TableError::ValueFailed { uuid, info: x.to_string() })?;
+    if let Some(tag_meta) = tag.meta_mut() {
+        *tag_meta = meta;
+    }
+    Ok(tag)
+}
+
+impl TagTables {
+    /// Tables of an arc volume with tags parents first, tag and item data are left out.
+    pub fn from_volume<TD, ID, VD, Body, Loader, AsyncLoader, TF>(volume: &ArcVolume<TD, ID, VD, Body, Loader, AsyncLoader, TF>) -> TableResult<Self>
+        where
+            TD: Debug + 'static,
+            ID: Debug + 'static,
+            VD: Debug,
+            Loader: Fn(&Hash) -> LoadResult<Body>,
+            AsyncLoader: Fn(&Hash) -> TF,
+            TF: Future<Output = LoadResult<Body>>
+    {
+        let mut tags = Vec::new();
+        let mut stack = vec![volume.root.as_ref()];
+        while let Some(tag) = stack.pop() {
+            tags.push(to_serde_tag(tag.proto.as_ref())
+                .map_err(|info| TableError::NotSerializable { uuid: *tag.proto.uuid(), info })?);
+            stack.extend(tag.children.values().rev().map(|x| x.as_ref()));
+        }
+        Ok(Self {
+            tags: SerdeTags(tags),
+            items: volume.items.values()
+                .map(|x| ItemRecord { uuid: x.uuid, body: x.body().copied() })
+                .collect(),
+            assignments: volume.items.values()
+                .flat_map(|item| item.tag_uuids().map(|tag| Assignment { item: item.uuid, tag: *tag }))
+                .collect(),
+        })
+    }
+
+    /// Parts to hydrate a volume from, the root is the first tag without a
+    /// parent and tags get the default data.
+    pub fn into_dry_volume<TD, ID, VD, F>(self, uuid: Uuid, data: VD, mut item_data: F) -> TableResult<DryVolume<TD, ID, VD>>
+        where F: FnMut(ItemRecord) -> ID
+    {
+        let root = *self.tags.iter().find(|x| x.parent().is_none()).context(RootNotFoundSnafu)?.uuid();
+        Ok(DryVolume {
+            uuid,
+            data,
+            root,
+            tags: self.tags,
+            tag_data: IndexMap::new(),
+            items: self.items.into_iter().map(|x| (x.uuid, item_data(x))).collect(),
+            assignments: self.assignments,
+        })
+    }
+
+    pub fn tags_batch(&self) -> TableResult<RecordBatch> {
+        let mut values = Vec::with_capacity(self.tags.0.len());
+        for tag in self.tags.iter() {
+            let value = serde_json::to_string(tag.payload())
+                .map_err(|x| TableError::ValueFailed { uuid: *tag.uuid(), info: x.to_string() })?;
+            values.push(Some(value));
+        }
+        let meta = |f: fn(&TagMeta) -> Option<u64>| self.tags.iter().map(move |x| x.meta().and_then(f));
+        let meta_str = |f: fn(&TagMeta) -> Option<&String>| self.tags.iter().map(move |x| x.meta().and_then(f).cloned());
+        RecordBatch::try_new(Arc::new(tags_schema()), vec![
+            strings(self.tags.iter().map(|x| Some(x.uuid().to_string()))),
+            strings(self.tags.iter().map(|x| x.parent().map(|x| x.to_string()))),
+            strings(self.tags.iter().map(|x| Some(x.tag_type_name().to_string()))),
+            strings(values),
+            timestamps(meta(|x| x.created)),
+            timestamps(meta(|x| x.modified)),
+            strings(meta_str(|x| x.author.as_ref())),
+            strings(meta_str(|x| x.source.as_ref())),
+        ]).context(ArrowFailedSnafu)
+    }
+
+    pub fn items_batch(&self) -> TableResult<RecordBatch> {
+        RecordBatch::try_new(Arc::new(items_schema()), vec![
+            strings(self.items.iter().map(|x| Some(x.uuid.to_string()))),
+            strings(self.items.iter().map(|x| x.body.map(|x| x.to_hex().to_string()))),
+        ]).context(ArrowFailedSnafu)
+    }
+
+    pub fn assignments_batch(&self) -> TableResult<RecordBatch> {
+        RecordBatch::try_new(Arc::new(assignments_schema()), vec![
+            strings(self.assignments.iter().map(|x| Some(x.item.to_string()))),
+            strings(self.assignments.iter().map(|x| Some(x.tag.to_string()))),
+        ]).context(ArrowFailedSnafu)
+    }
+
+    /// Read back tables in the layout of `tags_schema` and friends, columns
+    /// of other compatible types are cast and extra columns are ignored.
+    pub fn from_batches(tags: &[RecordBatch], items: &[RecordBatch], assignments: &[RecordBatch]) -> TableResult<Self> {
+        let mut result = Self {
+            tags: SerdeTags(Vec::new()),
+            items: Vec::new(),
+            assignments: Vec::new(),
+        };
+        for batch in tags {
+            let rows = string_column(batch, "uuid")?.into_iter()
+                .zip(string_column(batch, "parent")?)
+                .zip(string_column(batch, "type")?)
+                .zip(string_column(batch, "value")?)
+                .zip(timestamp_column(batch, "created")?)
+                .zip(timestamp_column(batch, "modified")?)
+                .zip(string_column(batch, "author")?)
+                .zip(string_column(batch, "source")?);
+            for (row, (((((((uuid, parent), tag_type), value), created), modified), author), source)) in rows.enumerate() {
+                let uuid = parse_uuid(&required(uuid, "uuid", row)?)?;
+                let parent = parent.map(|x| parse_uuid(&x)).transpose()?;
+                let meta = TagMeta { created, modified, author, source };
+                let tag_type = required(tag_type, "type", row)?;
+                let value = required(value, "value", row)?;
+                result.tags.0.push(read_tag(uuid, parent, &tag_type, &value, meta)?);
+            }
+        }
+        for batch in items {
+            let rows = string_column(batch, "uuid")?.into_iter()
+                .zip(string_column(batch, "body")?);
+            for (row, (uuid, body)) in rows.enumerate() {
+                result.items.push(ItemRecord {
+                    uuid: parse_uuid(&required(uuid, "uuid", row)?)?,
+                    body: body.map(|x| parse_hash(&x)).transpose()?,
+                });
+            }
+        }
+        for batch in assignments {
+            let rows = string_column(batch, "item")?.into_iter()
+                .zip(string_column(batch, "tag")?);
+            for (row, (item, tag)) in rows.enumerate() {
+                result.assignments.push(Assignment {
+                    item: parse_uuid(&required(item, "item", row)?)?,
+                    tag: parse_uuid(&required(tag, "tag", row)?)?,
+                });
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_io {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+
+    use snafu::prelude::*;
+
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::ChunkReader;
+
+    use super::{TagTables, TableError, TableResult, ArrowFailedSnafu, ParquetFailedSnafu};
+
+    pub const TAGS_FILE: &str = "tags.parquet";
+    pub const ITEMS_FILE: &str = "items.parquet";
+    pub const ASSIGNMENTS_FILE: &str = "assignments.parquet";
+
+    pub fn write_parquet<W: Write + Send>(batch: &RecordBatch, writer: W) -> TableResult<()> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(properties))
+            .context(ParquetFailedSnafu)?;
+        writer.write(batch).context(ParquetFailedSnafu)?;
+        writer.close().context(ParquetFailedSnafu)?;
+        Ok(())
+    }
+
+    pub fn read_parquet<R: ChunkReader + 'static>(reader: R) -> TableResult<Vec<RecordBatch>> {
+        ParquetRecordBatchReaderBuilder::try_new(reader)
+            .context(ParquetFailedSnafu)?
+            .build()
+            .context(ParquetFailedSnafu)?
+            .collect::<Result<Vec<_>, _>>()
+            .context(ArrowFailedSnafu)
+    }
+
+    fn create(path: &Path) -> TableResult<File> {
+        File::create(path).map_err(|error| TableError::IoFailed { error, info: path.display().to_string() })
+    }
+
+    fn open(path: &Path) -> TableResult<File> {
+        File::open(path).map_err(|error| TableError::IoFailed { error, info: path.display().to_string() })
+    }
+
+    impl TagTables {
+        /// Write `tags.parquet`, `items.parquet` and `assignments.parquet` into `dir`.
+        pub fn write_parquet_dir<P: AsRef<Path>>(&self, dir: P) -> TableResult<()> {
+            let dir = dir.as_ref();
+            write_parquet(&self.tags_batch()?, create(&dir.join(TAGS_FILE))?)?;
+            write_parquet(&self.items_batch()?, create(&dir.join(ITEMS_FILE))?)?;
+            write_parquet(&self.assignments_batch()?, create(&dir.join(ASSIGNMENTS_FILE))?)
+        }
+
+        pub fn read_parquet_dir<P: AsRef<Path>>(dir: P) -> TableResult<Self> {
+            let dir = dir.as_ref();
+            Self::from_batches(
+                &read_parquet(open(&dir.join(TAGS_FILE))?)?,
+                &read_parquet(open(&dir.join(ITEMS_FILE))?)?,
+                &read_parquet(open(&dir.join(ASSIGNMENTS_FILE))?)?,
+            )
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub use parquet_io::*;
+
+#[cfg(test)]
+mod tests {
+    use tag_proto::prelude::{ValTag, VecTag, IndexMapTag, Bytes};
+
+    use super::*;
+    use crate::arc::prelude::ProtoTag;
+    use crate::arc::editor::VolumeEditor;
+    use crate::testing::{self, assign, build, TestVolume};
+
+    tag_proto::register_serde_tag!(ValTag<Option<u32>>);
+
+    fn stamped<V>(mut tag: ValTag<V>) -> ValTag<V> {
+        tag.meta = TagMeta { created: Some(1_700_000_000_000 + tag.uuid.as_u128() as u64), ..Default::default() }.with_author("ann");
+        tag
+    }
+
+    fn stamped_tag(uuid: u128, parent: Option<u128>) -> Arc<ValTag<String>> {
+        Arc::new(stamped(ValTag::clone(&testing::tag(uuid, parent))))
+    }
+
+    fn val<V: Debug + Send + Sync + 'static>(uuid: u128, parent: u128, val: V) -> Arc<dyn ProtoTag + Send + Sync> {
+        Arc::new(stamped(ValTag { uuid: Uuid::from_u128(uuid), parent: Some(Uuid::from_u128(parent)), val, meta: Default::default() }))
+    }
+
+    fn volume() -> TestVolume<(), (), ()> {
+        let mut editor = VolumeEditor::<(), ()>::new(stamped_tag(1, None), ());
+        for (uuid, parent) in [(3, 1), (2, 1), (4, 2)] {
+            editor.add_tag(stamped_tag(uuid, Some(parent)), ()).unwrap();
+        }
+        assign(&mut editor, &[(11, 4), (11, 3), (10, 1)], ());
+        build(editor, 99, ())
+    }
+
+    fn typed_volume() -> TestVolume<(), (), ()> {
+        let mut editor = VolumeEditor::<(), ()>::new(stamped_tag(1, None), ());
+        editor.add_tag(val(2, 1, u64::MAX), ()).unwrap();
+        editor.add_tag(val(3, 2, -0.1f64), ()).unwrap();
+        editor.add_tag(val(4, 3, i64::MIN), ()).unwrap();
+        editor.add_tag(val(5, 4, None::<u32>), ()).unwrap();
+        editor.add_tag(val(6, 4, Some(7u32)), ()).unwrap();
+        editor.add_tag(val(7, 1, vec![Some("a".to_string()), None]), ()).unwrap();
+        editor.add_tag(val(8, 7, IndexMap::<String, u32>::from_iter([("b".to_string(), 2u32), ("a".to_string(), 1)])), ()).unwrap();
+        editor.add_tag(val(9, 8, Bytes(vec![0, 255])), ()).unwrap();
+        assign(&mut editor, &[(10, 6)], ());
+        build(editor, 99, ())
+    }
+
+    fn batches(tables: &TagTables) -> TagTables {
+        TagTables::from_batches(
+            &[tables.tags_batch().unwrap()],
+            &[tables.items_batch().unwrap()],
+            &[tables.assignments_batch().unwrap()],
+        ).unwrap()
+    }
+
+    fn parents(tags: &SerdeTags) -> Vec<(Uuid, Option<Uuid>)> {
+        tags.iter().map(|x| (*x.uuid(), x.parent().copied())).collect()
+    }
+
+    #[test]
+    fn typed_values_round_trip() {
+        let tables = TagTables::from_volume(&typed_volume()).unwrap();
+        let types: Vec<_> = tables.tags.iter().map(|x| x.tag_type_name().to_string()).collect();
+        assert_eq!(types, [
+            "ValTag<String>", "ValTag<u64>", "ValTag<f64>", "ValTag<i64>", "ValTag<Option<u32>>", "ValTag<Option<u32>>",
+            "ValTag<Vec<Option<String>>>", "ValTag<IndexMap<String, u32>>", "ValTag<Bytes>",
+        ]);
+        let loaded = batches(&tables);
+        assert_eq!(loaded, tables);
+        let val = |uuid: u128| loaded.tags.iter().find(|x| x.uuid() == &Uuid::from_u128(uuid)).unwrap();
+        assert_eq!(val(2).val::<u64>(), Some(&u64::MAX));
+        assert_eq!(val(3).val::<f64>(), Some(&-0.1));
+        assert_eq!(val(4).val::<i64>(), Some(&i64::MIN));
+        assert_eq!(val(5).val::<Option<u32>>(), Some(&None));
+        assert_eq!(val(6).val::<Option<u32>>(), Some(&Some(7)));
+        assert_eq!(val(7).downcast_ref::<VecTag<Option<String>>>().map(|x| &x.val), Some(&vec![Some("a".to_string()), None]));
+        let map = val(8).downcast_ref::<IndexMapTag<String, u32>>().map(|x| x.val.keys().cloned().collect::<Vec<_>>());
+        assert_eq!(map, Some(vec!["b".to_string(), "a".to_string()]));
+        assert_eq!(val(9).val::<Bytes>(), Some(&Bytes(vec![0, 255])));
+        assert_eq!(val(9).meta().and_then(|x| x.author.as_deref()), Some("ann"));
+        assert_eq!(parents(&loaded.tags), parents(&tables.tags));
+    }
+
+    #[test]
+    fn tables_match_dehydrated() {
+        let volume = typed_volume();
+        let dry = volume.dehydrate().unwrap();
+        let from_tables: DryVolume<(), (), ()> = batches(&TagTables::from_volume(&volume).unwrap())
+            .into_dry_volume(volume.uuid, (), |_| ())
+            .unwrap();
+        assert_eq!(from_tables.uuid, dry.uuid);
+        assert_eq!(from_tables.root, dry.root);
+        assert_eq!(parents(&from_tables.tags), parents(&dry.tags));
+        assert_eq!(from_tables.items.keys().collect::<Vec<_>>(), dry.items.keys().collect::<Vec<_>>());
+        assert_eq!(from_tables.assignments, dry.assignments);
+    }
+
+    #[test]
+    fn tags_need_a_root() {
+        let mut tables = TagTables::from_volume(&volume()).unwrap();
+        tables.tags.0.remove(0);
+        assert!(matches!(tables.into_dry_volume::<(), (), (), _>(Uuid::from_u128(99), (), |_| ()), Err(TableError::RootNotFound)));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip() {
+        let volume = volume();
+        let tables = TagTables::from_volume(&volume).unwrap();
+        assert_eq!(tables.tags.iter().map(|x| *x.uuid()).collect::<Vec<_>>(), [1, 3, 2, 4].map(Uuid::from_u128));
+
+        let dir = std::env::temp_dir().join(format!("tag-table-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        tables.write_parquet_dir(&dir).unwrap();
+        let loaded = TagTables::read_parquet_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, tables);
+
+        let rebuilt = testing::hydrate(loaded.into_dry_volume::<(), (), (), _>(Uuid::from_u128(99), (), |_| ()).unwrap());
+        assert_eq!(TagTables::from_volume(&rebuilt).unwrap(), tables);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn typed_values_round_trip_parquet() {
+        let tables = TagTables::from_volume(&typed_volume()).unwrap();
+        let dir = std::env::temp_dir().join(format!("tag-table-typed-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        tables.write_parquet_dir(&dir).unwrap();
+        let loaded = TagTables::read_parquet_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), tables);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt::Debug;
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use tag_proto::prelude::ValTag;
+
+use crate::prelude::{Uuid, Hash, LoadResult};
+use crate::arc::prelude::{Volume, VolumeEditor};
+
+#[cfg(feature = "serde")]
+use crate::arc::prelude::DryVolume;
+
+pub type Loader = fn(&Hash) -> LoadResult<()>;
+pub type AsyncLoader = fn(&Hash) -> Ready<LoadResult<()>>;
+pub type TestVolume<TD, ID, VD> = Volume<TD, ID, VD, (), Loader, AsyncLoader, Ready<LoadResult<()>>>;
+
+pub fn load(_: &Hash) -> LoadResult<()> {
+    Ok(())
+}
+
+pub fn load_async(_: &Hash) -> Ready<LoadResult<()>> {
+    ready(Ok(()))
+}
+
+/// A tag holding its uuid as text.
+pub fn tag(uuid: u128, parent: Option<u128>) -> Arc<ValTag<String>> {
+    Arc::new(ValTag { uuid: Uuid::from_u128(uuid), parent: parent.map(Uuid::from_u128), val: uuid.to_string(), meta: Default::default() })
+}
+
+/// Build with the loaders above, so the volume type can be named.
+pub fn build<TD: Debug, ID: Debug, VD: Debug>(editor: VolumeEditor<TD, ID>, uuid: u128, data: VD) -> TestVolume<TD, ID, VD> {
+    editor.build(Uuid::from_u128(uuid), data, load as Loader, load_async as AsyncLoader).unwrap()
+}
+
+/// Hydrate and build with the loaders above, failing on any dangling reference.
+#[cfg(feature = "serde")]
+pub fn hydrate<TD: Debug + Default, ID: Debug, VD: Debug>(dry: DryVolume<TD, ID, VD>) -> TestVolume<TD, ID, VD> {
+    let hydrated = dry.hydrate().unwrap();
+    assert!(hydrated.report.is_clean(), "{:?}", hydrated.report);
+    hydrated.build(load as Loader, load_async as AsyncLoader).unwrap()
+}
+
+/// Assign `(item, tag)` pairs, adding items not there yet with `data`.
+pub fn assign<TD: Debug, ID: Debug + Clone>(editor: &mut VolumeEditor<TD, ID>, pairs: &[(u128, u128)], data: ID) {
+    for (item, tag) in pairs {
+        let item = Uuid::from_u128(*item);
+        if !editor.contains_item(&item) {
+            editor.add_item(item, data.clone()).unwrap();
+        }
+        editor.assign(&item, &Uuid::from_u128(*tag)).unwrap();
+    }
+}
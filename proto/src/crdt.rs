@@ -0,0 +1,362 @@
+use alloc::boxed::Box;
+use core::fmt::Debug;
+
+use crate::prelude::{Uuid, ValTag, TagMeta, Timestamp, Tags, IndexMap, IndexSet};
+
+#[cfg(feature = "std")]
+use crate::meta::now;
+
+/// Lamport counter plus replica, totally ordered so every replica picks the same winner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Stamp {
+    pub counter: u64,
+    pub replica: Uuid,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LwwRegister<T> {
+    pub stamp: Stamp,
+    pub value: T,
+}
+
+impl<T: Clone> LwwRegister<T> {
+    pub fn merge(&mut self, other: &Self) {
+        if other.stamp > self.stamp {
+            *self = other.clone();
+        }
+    }
+}
+
+/// Membership is an observed-remove set of add stamps, so a remove only
+/// tombstones the adds it has seen and a concurrent add wins.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct CrdtEntry<V> {
+    pub adds: IndexSet<Stamp>,
+    pub tombstones: IndexSet<Stamp>,
+    pub parent: LwwRegister<Option<Uuid>>,
+    pub val: LwwRegister<V>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meta: LwwRegister<TagMeta>,
+}
+
+impl<V: Clone> CrdtEntry<V> {
+    pub fn is_present(&self) -> bool {
+        self.adds.iter().any(|x| !self.tombstones.contains(x))
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.adds.extend(other.adds.iter().copied());
+        self.tombstones.extend(other.tombstones.iter().copied());
+        self.parent.merge(&other.parent);
+        self.val.merge(&other.val);
+        self.meta.merge(&other.meta);
+    }
+}
+
+/// Tag collection that can be edited on several replicas and merged in any
+/// order, every replica that has seen the same edits ends up with the same tags.
+///
+/// Removed tags are kept as tombstones so later merges don't bring them back.
+/// Concurrent moves can leave parents in a cycle, reading the tags drops the
+/// parent with the lowest stamp in each cycle so every replica sees the same tree.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct CrdtTags<V> {
+    replica: Uuid,
+    counter: u64,
+    entries: IndexMap<Uuid, CrdtEntry<V>>,
+}
+
+impl<V: Clone> CrdtTags<V> {
+    pub fn new(replica: Uuid) -> Self {
+        Self {
+            replica,
+            counter: 0,
+            entries: IndexMap::default(),
+        }
+    }
+
+    pub fn replica(&self) -> &Uuid {
+        &self.replica
+    }
+
+    /// Continue editing a state loaded from elsewhere as another replica.
+    pub fn with_replica(mut self, replica: Uuid) -> Self {
+        self.replica = replica;
+        self
+    }
+
+    fn next_stamp(&mut self) -> Stamp {
+        self.counter += 1;
+        Stamp {
+            counter: self.counter,
+            replica: self.replica,
+        }
+    }
+
+    pub fn entry(&self, uuid: &Uuid) -> Option<&CrdtEntry<V>> {
+        self.entries.get(uuid)
+    }
+
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.entry(uuid).map(|x| x.is_present()).unwrap_or(false)
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<ValTag<V>> {
+        self.entry(uuid)
+            .filter(|x| x.is_present())
+            .map(|x| ValTag {
+                uuid: *uuid,
+                parent: self.parent_of(uuid, x),
+                val: x.val.value.clone(),
+                meta: x.meta.value.clone(),
+            })
+    }
+
+    // the parent unless its edge has the lowest stamp of a cycle, cycles are
+    // disjoint as every tag has one parent
+    fn parent_of(&self, uuid: &Uuid, entry: &CrdtEntry<V>) -> Option<Uuid> {
+        let parent = entry.parent.value?;
+        let mut lowest = entry.parent.stamp;
+        let mut current = parent;
+        for _ in 0..self.entries.len() {
+            if current == *uuid {
+                return (lowest != entry.parent.stamp).then_some(parent);
+            }
+            let Some(next) = self.entries.get(&current).filter(|x| x.is_present()) else {
+                break;
+            };
+            lowest = lowest.min(next.parent.stamp);
+            let Some(next) = next.parent.value else {
+                break;
+            };
+            current = next;
+        }
+        Some(parent)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ValTag<V>> + '_ {
+        self.entries.keys().filter_map(|x| self.get(x))
+    }
+
+    /// Every insert is a new add, so it survives a concurrent remove.
+    pub fn insert(&mut self, tag: ValTag<V>) {
+        let stamp = self.next_stamp();
+        match self.entries.get_mut(&tag.uuid) {
+            Some(entry) => {
+                entry.adds.insert(stamp);
+                entry.parent = LwwRegister { stamp, value: tag.parent };
+                entry.val = LwwRegister { stamp, value: tag.val };
+                entry.meta = LwwRegister { stamp, value: tag.meta };
+            },
+            None => {
+                self.entries.insert(tag.uuid, CrdtEntry {
+                    adds: IndexSet::from_iter([stamp]),
+                    tombstones: IndexSet::default(),
+                    parent: LwwRegister { stamp, value: tag.parent },
+                    val: LwwRegister { stamp, value: tag.val },
+                    meta: LwwRegister { stamp, value: tag.meta },
+                });
+            },
+        }
+    }
+
+    /// Insert as an edit, stamping the tag's metadata, see `Tags::upsert`.
+    #[cfg(feature = "std")]
+    pub fn upsert(&mut self, tag: ValTag<V>) {
+        self.upsert_at(tag, now())
+    }
+
+    pub fn upsert_at(&mut self, mut tag: ValTag<V>, at: Timestamp) {
+        let old = self.entry(&tag.uuid).filter(|x| x.is_present()).map(|x| &x.meta.value);
+        tag.meta.stamp(old, at);
+        self.insert(tag);
+    }
+
+    /// Edit a present tag and set its `modified` time, returns false if it isn't present.
+    #[cfg(feature = "std")]
+    pub fn update<F: FnOnce(&mut ValTag<V>)>(&mut self, uuid: &Uuid, edit: F) -> bool {
+        self.update_at(uuid, now(), edit)
+    }
+
+    /// Like `Tags::update_at`, changing the uuid is ignored.
+    pub fn update_at<F: FnOnce(&mut ValTag<V>)>(&mut self, uuid: &Uuid, at: Timestamp, edit: F) -> bool {
+        let Some(mut tag) = self.get(uuid) else {
+            return false;
+        };
+        let parent = tag.parent;
+        edit(&mut tag);
+        tag.meta.modified = Some(at);
+        let stamp = self.next_stamp();
+        if let Some(entry) = self.entries.get_mut(uuid) {
+            // an unchanged parent keeps its stamp so concurrent moves still win
+            if tag.parent != parent {
+                entry.parent = LwwRegister { stamp, value: tag.parent };
+            }
+            entry.val = LwwRegister { stamp, value: tag.val };
+            entry.meta = LwwRegister { stamp, value: tag.meta };
+        }
+        true
+    }
+
+    /// Returns false if the tag isn't present.
+    pub fn set_parent(&mut self, uuid: &Uuid, parent: Option<Uuid>) -> bool {
+        if !self.contains(uuid) {
+            return false;
+        }
+        let stamp = self.next_stamp();
+        if let Some(entry) = self.entries.get_mut(uuid) {
+            entry.parent = LwwRegister { stamp, value: parent };
+        }
+        true
+    }
+
+    /// Returns false if the tag isn't present.
+    pub fn set_val(&mut self, uuid: &Uuid, val: V) -> bool {
+        if !self.contains(uuid) {
+            return false;
+        }
+        let stamp = self.next_stamp();
+        if let Some(entry) = self.entries.get_mut(uuid) {
+            entry.val = LwwRegister { stamp, value: val };
+        }
+        true
+    }
+
+    /// Returns false if the tag isn't present.
+    pub fn set_meta(&mut self, uuid: &Uuid, meta: TagMeta) -> bool {
+        if !self.contains(uuid) {
+            return false;
+        }
+        let stamp = self.next_stamp();
+        if let Some(entry) = self.entries.get_mut(uuid) {
+            entry.meta = LwwRegister { stamp, value: meta };
+        }
+        true
+    }
+
+    /// Returns false if the tag isn't present.
+    pub fn remove(&mut self, uuid: &Uuid) -> bool {
+        match self.entries.get_mut(uuid) {
+            Some(entry) if entry.is_present() => {
+                let adds = entry.adds.clone();
+                entry.tombstones.extend(adds);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Merge another replica's state, entries end up sorted by uuid.
+    ///
+    /// Metadata comes with the edits that win, stamped on the replica that made them.
+    pub fn merge(&mut self, other: &Self) {
+        self.counter = self.counter.max(other.counter);
+        for (uuid, entry) in other.entries.iter() {
+            match self.entries.get_mut(uuid) {
+                Some(local) => local.merge(entry),
+                None => {
+                    self.entries.insert(*uuid, entry.clone());
+                },
+            }
+        }
+        self.entries.sort_keys();
+    }
+
+    /// Same tags and history, regardless of which replica holds them.
+    pub fn same_state(&self, other: &Self) -> bool
+        where V: PartialEq
+    {
+        self.entries == other.entries
+    }
+}
+
+impl<V: Clone + Debug + 'static> CrdtTags<V> {
+    pub fn to_tags(&self) -> Tags<ValTag<V>> {
+        self.iter().map(Box::new).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::*;
+
+    fn tag(uuid: u128, parent: Option<u128>) -> ValTag<String> {
+        ValTag { uuid: Uuid::from_u128(uuid), parent: parent.map(Uuid::from_u128), val: uuid.to_string(), meta: TagMeta::default() }
+    }
+
+    #[test]
+    fn concurrent_moves_dont_cycle() {
+        let mut a = CrdtTags::new(Uuid::from_u128(100));
+        a.insert(tag(1, None));
+        a.insert(tag(2, Some(1)));
+        a.insert(tag(3, Some(1)));
+        let mut b = a.clone().with_replica(Uuid::from_u128(200));
+        assert!(a.set_parent(&Uuid::from_u128(2), Some(Uuid::from_u128(3))));
+        assert!(b.set_parent(&Uuid::from_u128(3), Some(Uuid::from_u128(2))));
+
+        let mut ab = a.clone();
+        ab.merge(&b);
+        let mut ba = b.clone();
+        ba.merge(&a);
+        assert!(ab.same_state(&ba));
+        for tags in [&ab, &ba] {
+            // both moves have counter 4, replica 100 has the lower stamp
+            assert_eq!(tags.get(&Uuid::from_u128(2)).map(|x| x.parent), Some(None));
+            assert_eq!(tags.get(&Uuid::from_u128(3)).map(|x| x.parent), Some(Some(Uuid::from_u128(2))));
+            assert!(tags.to_tags().validate().is_valid());
+        }
+    }
+
+    #[test]
+    fn reinsert_wins_over_concurrent_remove() {
+        let mut a = CrdtTags::new(Uuid::from_u128(100));
+        a.insert(tag(1, None));
+        let mut b = a.clone().with_replica(Uuid::from_u128(200));
+        assert!(b.remove(&Uuid::from_u128(1)));
+        let mut edited = tag(1, None);
+        edited.val = "again".to_string();
+        a.insert(edited);
+
+        let mut ab = a.clone();
+        ab.merge(&b);
+        let mut ba = b.clone();
+        ba.merge(&a);
+        assert!(ab.same_state(&ba));
+        for tags in [&ab, &ba] {
+            assert_eq!(tags.get(&Uuid::from_u128(1)).map(|x| x.val), Some("again".to_string()));
+        }
+
+        // a remove that has seen the re-insert removes it
+        assert!(ab.remove(&Uuid::from_u128(1)));
+        ba.merge(&ab);
+        assert!(!ba.contains(&Uuid::from_u128(1)));
+    }
+
+    #[test]
+    fn edits_are_stamped() {
+        let mut a = CrdtTags::new(Uuid::from_u128(100));
+        a.upsert_at(tag(1, None), 10);
+        assert!(a.update_at(&Uuid::from_u128(1), 20, |x| x.val = "x".to_string()));
+        let mut b = CrdtTags::new(Uuid::from_u128(200));
+        b.merge(&a);
+        let meta = b.get(&Uuid::from_u128(1)).map(|x| x.meta);
+        assert_eq!(meta, Some(TagMeta { created: Some(10), modified: Some(20), ..Default::default() }));
+    }
+
+    #[test]
+    fn meta_is_replicated() {
+        let mut a = CrdtTags::new(Uuid::from_u128(100));
+        a.insert(tag(1, None));
+        let mut b = CrdtTags::new(Uuid::from_u128(200));
+        b.merge(&a);
+        assert!(b.set_meta(&Uuid::from_u128(1), TagMeta::default().with_author("bob")));
+        a.merge(&b);
+        assert_eq!(a.get(&Uuid::from_u128(1)).and_then(|x| x.meta.author), Some("bob".to_string()));
+    }
+}
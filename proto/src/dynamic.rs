@@ -0,0 +1,455 @@
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::hash::Hash;
+
+#[cfg(feature = "std")]
+use std::collections::{HashSet, HashMap};
+
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, IndexSet, IndexMap, ValTag};
+
+/// Schemaless tag value, for tags defined at runtime.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(#[cfg_attr(feature = "serde", serde(with = "crate::serde::value::bytes"))] Vec<u8>),
+    List(Vec<Value>),
+    Map(IndexMap<String, Value>),
+    Uuid(Uuid),
+}
+
+pub type DynTag = ValTag<Value>;
+
+/// Bytes as one value, `Vec<u8>` converts to a list of ints instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize), serde(transparent))]
+pub struct Bytes(#[cfg_attr(feature = "serde", serde(with = "crate::serde::value::bytes"))] pub Vec<u8>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(v: Vec<u8>) -> Self {
+        Self(v)
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ValueError {
+    #[snafu(display("Type mismatch: expected `{}`, got `{}`", expected, actual))]
+    TypeMismatch { expected: &'static str, actual: &'static str },
+    #[snafu(display("Out of range: `{}` -> `{}`", value, expected))]
+    OutOfRange { value: String, expected: &'static str },
+}
+
+impl From<Infallible> for ValueError {
+    fn from(v: Infallible) -> Self {
+        match v {}
+    }
+}
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Bytes(_) => "bytes",
+            Self::List(_) => "list",
+            Self::Map(_) => "map",
+            Self::Uuid(_) => "uuid",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    fn mismatch<T>(&self, expected: &'static str) -> Result<T, ValueError> {
+        TypeMismatchSnafu { expected, actual: self.kind() }.fail()
+    }
+}
+
+impl<V: Into<Value>> ValTag<V> {
+    pub fn into_dyn(self) -> DynTag {
+        ValTag {
+            uuid: self.uuid,
+            parent: self.parent,
+            val: self.val.into(),
+            meta: self.meta,
+        }
+    }
+}
+
+impl<V> ValTag<V>
+    where
+        V: TryInto<Value>,
+        V::Error: Into<ValueError>,
+{
+    /// Like `into_dyn`, also for values that may not fit, e.g. `u64` beyond `i64`.
+    pub fn try_into_dyn(self) -> Result<DynTag, ValueError> {
+        Ok(ValTag {
+            uuid: self.uuid,
+            parent: self.parent,
+            val: self.val.try_into().map_err(Into::into)?,
+            meta: self.meta,
+        })
+    }
+}
+
+impl DynTag {
+    pub fn try_into_typed<V>(self) -> Result<ValTag<V>, ValueError>
+        where V: TryFrom<Value, Error = ValueError>
+    {
+        Ok(ValTag {
+            uuid: self.uuid,
+            parent: self.parent,
+            val: V::try_from(self.val)?,
+            meta: self.meta,
+        })
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(v) => Ok(v),
+            other => other.mismatch("bool"),
+        }
+    }
+}
+
+macro_rules! impl_int_value {
+    ($($int_type: ident),*) => {
+        $(
+            impl TryFrom<Value> for $int_type {
+                type Error = ValueError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::Int(v) => $int_type::try_from(v).map_err(|_| ValueError::OutOfRange {
+                            value: v.to_string(),
+                            expected: stringify!($int_type),
+                        }),
+                        other => other.mismatch(stringify!($int_type)),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! impl_lossless_int_value {
+    ($($int_type: ident),*) => {
+        $(
+            impl From<$int_type> for Value {
+                fn from(v: $int_type) -> Self {
+                    Self::Int(v.into())
+                }
+            }
+        )*
+        impl_int_value!($($int_type),*);
+    }
+}
+
+macro_rules! impl_lossy_int_value {
+    ($($int_type: ident),*) => {
+        $(
+            impl TryFrom<$int_type> for Value {
+                type Error = ValueError;
+
+                fn try_from(v: $int_type) -> Result<Self, Self::Error> {
+                    i64::try_from(v).map(Self::Int).map_err(|_| ValueError::OutOfRange {
+                        value: v.to_string(),
+                        expected: "i64",
+                    })
+                }
+            }
+        )*
+        impl_int_value!($($int_type),*);
+    }
+}
+
+impl_lossless_int_value!(u8, i8, u16, i16, u32, i32, i64);
+impl_lossy_int_value!(u64, u128, i128);
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Self::Float(v.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl TryFrom<Value> for f32 {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(v) if v.is_nan() || (v as f32) as f64 == v => Ok(v as f32),
+            Value::Float(v) => OutOfRangeSnafu { value: v.to_string(), expected: "f32" }.fail(),
+            other => other.mismatch("f32"),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(v) => Ok(v),
+            other => other.mismatch("f64"),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_owned())
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(v) => Ok(v),
+            other => other.mismatch("string"),
+        }
+    }
+}
+
+impl From<Bytes> for Value {
+    fn from(v: Bytes) -> Self {
+        Self::Bytes(v.0)
+    }
+}
+
+impl TryFrom<Value> for Bytes {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(v) => Ok(Self(v)),
+            other => other.mismatch("bytes"),
+        }
+    }
+}
+
+impl From<Uuid> for Value {
+    fn from(v: Uuid) -> Self {
+        Self::Uuid(v)
+    }
+}
+
+impl TryFrom<Value> for Uuid {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Uuid(v) => Ok(v),
+            other => other.mismatch("uuid"),
+        }
+    }
+}
+
+impl<V: Into<Value>> From<Option<V>> for Value {
+    fn from(v: Option<V>) -> Self {
+        v.map(Into::into).unwrap_or_default()
+    }
+}
+
+impl<V> TryFrom<Value> for Option<V>
+    where V: TryFrom<Value, Error = ValueError>
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Null => Ok(None),
+            other => V::try_from(other).map(Some),
+        }
+    }
+}
+
+fn try_from_list<V, C>(value: Value) -> Result<C, ValueError>
+    where
+        V: TryFrom<Value, Error = ValueError>,
+        C: FromIterator<V>,
+{
+    match value {
+        Value::List(v) => v.into_iter().map(V::try_from).collect(),
+        other => other.mismatch("list"),
+    }
+}
+
+fn try_from_map<V, C>(value: Value) -> Result<C, ValueError>
+    where
+        V: TryFrom<Value, Error = ValueError>,
+        C: FromIterator<(String, V)>,
+{
+    match value {
+        Value::Map(v) => v.into_iter().map(|(k, v)| Ok((k, V::try_from(v)?))).collect(),
+        other => other.mismatch("map"),
+    }
+}
+
+impl<V: Into<Value>> From<Vec<V>> for Value {
+    fn from(v: Vec<V>) -> Self {
+        Self::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<V> TryFrom<Value> for Vec<V>
+    where V: TryFrom<Value, Error = ValueError>
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        try_from_list(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Into<Value>> From<HashSet<V>> for Value {
+    fn from(v: HashSet<V>) -> Self {
+        Self::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> TryFrom<Value> for HashSet<V>
+    where V: TryFrom<Value, Error = ValueError> + Eq + Hash
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        try_from_list(value)
+    }
+}
+
+impl<V: Into<Value>> From<IndexSet<V>> for Value {
+    fn from(v: IndexSet<V>) -> Self {
+        Self::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<V> TryFrom<Value> for IndexSet<V>
+    where V: TryFrom<Value, Error = ValueError> + Eq + Hash
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        try_from_list(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Into<Value>> From<HashMap<String, V>> for Value {
+    fn from(v: HashMap<String, V>) -> Self {
+        Self::Map(v.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> TryFrom<Value> for HashMap<String, V>
+    where V: TryFrom<Value, Error = ValueError>
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        try_from_map(value)
+    }
+}
+
+impl<V: Into<Value>> From<IndexMap<String, V>> for Value {
+    fn from(v: IndexMap<String, V>) -> Self {
+        Self::Map(v.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl<V> TryFrom<Value> for IndexMap<String, V>
+    where V: TryFrom<Value, Error = ValueError>
+{
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        try_from_map(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes = Bytes(vec![0, 1, 255]);
+        let value = Value::from(bytes.clone());
+        assert_eq!(value, Value::Bytes(vec![0, 1, 255]));
+        assert_eq!(Bytes::try_from(value).ok(), Some(bytes));
+        assert!(Bytes::try_from(Value::Int(1)).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bytes_json_round_trip() {
+        let value = Value::Bytes(vec![0, 1, 255]);
+        let json = serde_json::to_string(&value).ok();
+        let back = json.and_then(|x| serde_json::from_str::<Value>(&x).ok());
+        assert_eq!(back, Some(value));
+    }
+
+    fn tag<V>(val: V) -> ValTag<V> {
+        ValTag { uuid: Uuid::nil(), parent: None, val, meta: Default::default() }
+    }
+
+    #[test]
+    fn try_into_dyn_checks_range() {
+        let t = tag(1u64);
+        assert_eq!(t.try_into_dyn().ok().map(|x| x.val), Some(Value::Int(1)));
+        let t = tag(u64::MAX);
+        assert!(matches!(t.try_into_dyn(), Err(ValueError::OutOfRange { .. })));
+        let t = tag(String::from("a"));
+        assert_eq!(t.try_into_dyn().ok().map(|x| x.val), Some(Value::String(String::from("a"))));
+    }
+
+    #[test]
+    fn f32_rejects_lossy_floats() {
+        assert_eq!(f32::try_from(Value::Float(0.5)).ok(), Some(0.5));
+        assert!(f32::try_from(Value::Float(f64::NAN)).is_ok_and(f32::is_nan));
+        assert!(f32::try_from(Value::Float(f64::INFINITY)).is_ok());
+        assert!(matches!(f32::try_from(Value::Float(0.1)), Err(ValueError::OutOfRange { .. })));
+        assert!(matches!(f32::try_from(Value::Float(1e300)), Err(ValueError::OutOfRange { .. })));
+    }
+}
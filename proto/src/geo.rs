@@ -0,0 +1,327 @@
+use std::any::Any;
+use std::fmt::Debug;
+
+use crate::prelude::{Tag, ValTag};
+
+/// Mean earth radius in meters, distances use the haversine formula on a sphere.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// WGS84 coordinates in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Point {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Latitude/longitude box, boxes don't wrap around the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct BBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+/// Exterior ring plus holes, rings are implicitly closed.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Polygon {
+    pub exterior: Vec<Point>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub holes: Vec<Vec<Point>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Geometry {
+    Point(Point),
+    Polygon(Polygon),
+}
+
+pub type PointTag = ValTag<Point>;
+pub type PolygonTag = ValTag<Polygon>;
+pub type GeometryTag = ValTag<Geometry>;
+
+impl Point {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon }
+    }
+
+    pub fn distance_to(&self, other: &Point) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    // equirectangular projection in meters around `origin`, fine for short distances
+    fn project(&self, origin: &Point) -> (f64, f64) {
+        let x = ((self.lon - origin.lon + 180.0).rem_euclid(360.0) - 180.0).to_radians() * origin.lat.to_radians().cos() * EARTH_RADIUS;
+        let y = (self.lat - origin.lat).to_radians() * EARTH_RADIUS;
+        (x, y)
+    }
+}
+
+impl BBox {
+    pub fn new(min: Point, max: Point) -> Self {
+        Self { min, max }
+    }
+
+    /// Boxes containing every point within `radius` meters of `center`, two
+    /// when the circle crosses the antimeridian.
+    pub fn around(center: &Point, radius: f64) -> Vec<Self> {
+        let d_lat = (radius / EARTH_RADIUS).to_degrees();
+        let min_lat = (center.lat - d_lat).max(-90.0);
+        let max_lat = (center.lat + d_lat).min(90.0);
+        let widest = min_lat.abs().max(max_lat.abs()).to_radians().cos();
+        let d_lon = if widest > f64::EPSILON { d_lat / widest } else { 180.0 };
+        let (min_lon, max_lon) = (center.lon - d_lon, center.lon + d_lon);
+        let bbox = |min_lon, max_lon| Self::new(Point::new(min_lat, min_lon), Point::new(max_lat, max_lon));
+        if d_lon >= 180.0 {
+            vec![bbox(-180.0, 180.0)]
+        } else if min_lon < -180.0 {
+            vec![bbox(min_lon + 360.0, 180.0), bbox(-180.0, max_lon)]
+        } else if max_lon > 180.0 {
+            vec![bbox(min_lon, 180.0), bbox(-180.0, max_lon - 360.0)]
+        } else {
+            vec![bbox(min_lon, max_lon)]
+        }
+    }
+
+    pub fn of_points<'a, I: IntoIterator<Item = &'a Point>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(Self::new(first, first), |mut bbox, x| {
+            bbox.min.lat = bbox.min.lat.min(x.lat);
+            bbox.min.lon = bbox.min.lon.min(x.lon);
+            bbox.max.lat = bbox.max.lat.max(x.lat);
+            bbox.max.lon = bbox.max.lon.max(x.lon);
+            bbox
+        }))
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        point.lat >= self.min.lat && point.lat <= self.max.lat
+            && point.lon >= self.min.lon && point.lon <= self.max.lon
+    }
+
+    pub fn intersects(&self, other: &BBox) -> bool {
+        self.min.lat <= other.max.lat && other.min.lat <= self.max.lat
+            && self.min.lon <= other.max.lon && other.min.lon <= self.max.lon
+    }
+}
+
+fn ring_contains(ring: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    for (index, a) in ring.iter().enumerate() {
+        let b = &ring[(index + 1) % ring.len()];
+        if (a.lat > point.lat) != (b.lat > point.lat) {
+            let lon = a.lon + (point.lat - a.lat) / (b.lat - a.lat) * (b.lon - a.lon);
+            if point.lon < lon {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn ring_near(ring: &[Point], center: &Point, radius: f64) -> bool {
+    ring.iter().enumerate().any(|(index, a)| {
+        let b = &ring[(index + 1) % ring.len()];
+        let ((ax, ay), (bx, by)) = (a.project(center), b.project(center));
+        let (dx, dy) = (bx - ax, by - ay);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 { (-(ax * dx + ay * dy) / length).clamp(0.0, 1.0) } else { 0.0 };
+        (ax + t * dx).hypot(ay + t * dy) <= radius
+    })
+}
+
+impl Polygon {
+    pub fn new(exterior: Vec<Point>) -> Self {
+        Self { exterior, holes: Vec::new() }
+    }
+
+    /// Even-odd test in plain lat/lon, points on the boundary may go either way.
+    pub fn contains(&self, point: &Point) -> bool {
+        ring_contains(&self.exterior, point)
+            && !self.holes.iter().any(|x| ring_contains(x, point))
+    }
+}
+
+/// Anything that can be found with bounding box and radius queries.
+pub trait Spatial {
+    fn bbox(&self) -> Option<BBox>;
+    fn intersects_bbox(&self, bbox: &BBox) -> bool;
+
+    /// Whether any part is within `radius` meters of `center`, edges are
+    /// measured in a local projection so long edges are approximate.
+    fn within_radius(&self, center: &Point, radius: f64) -> bool;
+}
+
+impl Spatial for Point {
+    fn bbox(&self) -> Option<BBox> {
+        Some(BBox::new(*self, *self))
+    }
+
+    fn intersects_bbox(&self, bbox: &BBox) -> bool {
+        bbox.contains(self)
+    }
+
+    fn within_radius(&self, center: &Point, radius: f64) -> bool {
+        self.distance_to(center) <= radius
+    }
+}
+
+impl Spatial for Polygon {
+    fn bbox(&self) -> Option<BBox> {
+        BBox::of_points(self.exterior.iter())
+    }
+
+    fn intersects_bbox(&self, bbox: &BBox) -> bool {
+        let Some(own) = self.bbox() else {
+            return false;
+        };
+        if !own.intersects(bbox) {
+            return false;
+        }
+        let corners = [
+            bbox.min,
+            Point::new(bbox.min.lat, bbox.max.lon),
+            bbox.max,
+            Point::new(bbox.max.lat, bbox.min.lon),
+        ];
+        self.exterior.iter().any(|x| bbox.contains(x))
+            || corners.iter().any(|x| self.contains(x))
+            || rings_cross(&self.exterior, &corners)
+    }
+
+    fn within_radius(&self, center: &Point, radius: f64) -> bool {
+        !self.exterior.is_empty() && (self.contains(center)
+            || ring_near(&self.exterior, center, radius)
+            || self.holes.iter().any(|x| ring_near(x, center, radius)))
+    }
+}
+
+fn rings_cross(a: &[Point], b: &[Point]) -> bool {
+    let edges = |ring: &[Point]| -> Vec<(Point, Point)> {
+        (0..ring.len()).map(|i| (ring[i], ring[(i + 1) % ring.len()])).collect()
+    };
+    let side = |a: &Point, b: &Point, c: &Point| {
+        (b.lon - a.lon) * (c.lat - a.lat) - (b.lat - a.lat) * (c.lon - a.lon)
+    };
+    let b_edges = edges(b);
+    edges(a).iter().any(|(p1, p2)| {
+        b_edges.iter().any(|(q1, q2)| {
+            side(p1, p2, q1) * side(p1, p2, q2) < 0.0 && side(q1, q2, p1) * side(q1, q2, p2) < 0.0
+        })
+    })
+}
+
+impl Spatial for Geometry {
+    fn bbox(&self) -> Option<BBox> {
+        match self {
+            Self::Point(v) => v.bbox(),
+            Self::Polygon(v) => v.bbox(),
+        }
+    }
+
+    fn intersects_bbox(&self, bbox: &BBox) -> bool {
+        match self {
+            Self::Point(v) => v.intersects_bbox(bbox),
+            Self::Polygon(v) => v.intersects_bbox(bbox),
+        }
+    }
+
+    fn within_radius(&self, center: &Point, radius: f64) -> bool {
+        match self {
+            Self::Point(v) => v.within_radius(center, radius),
+            Self::Polygon(v) => v.within_radius(center, radius),
+        }
+    }
+}
+
+impl From<Point> for Geometry {
+    fn from(v: Point) -> Self {
+        Self::Point(v)
+    }
+}
+
+impl From<Polygon> for Geometry {
+    fn from(v: Polygon) -> Self {
+        Self::Polygon(v)
+    }
+}
+
+/// A tag that may carry a geometry, the dyn tag impls look for
+/// `PointTag`, `PolygonTag` and `GeometryTag`.
+pub trait SpatialTag : Tag {
+    fn geometry(&self) -> Option<Geometry>;
+}
+
+impl<V> SpatialTag for ValTag<V>
+    where V: Clone + Debug + Into<Geometry> + 'static
+{
+    fn geometry(&self) -> Option<Geometry> {
+        Some(self.val.clone().into())
+    }
+}
+
+fn geometry_of(tag: &dyn Any) -> Option<Geometry> {
+    if let Some(tag) = tag.downcast_ref::<PointTag>() {
+        return Some(tag.val.into());
+    }
+    if let Some(tag) = tag.downcast_ref::<PolygonTag>() {
+        return Some(tag.val.clone().into());
+    }
+    tag.downcast_ref::<GeometryTag>().map(|x| x.val.clone())
+}
+
+macro_rules! impl_dyn_spatial_tag {
+    ($($dyn_type: ty),* $(,)?) => {
+        $(
+            impl SpatialTag for $dyn_type {
+                fn geometry(&self) -> Option<Geometry> {
+                    geometry_of(self)
+                }
+            }
+        )*
+    }
+}
+
+impl_dyn_spatial_tag!(
+    dyn Tag,
+    dyn Tag + Send,
+    dyn Tag + Send + Sync,
+);
+
+#[cfg(feature = "serde")]
+impl_dyn_spatial_tag!(
+    dyn crate::serde::SerdeTag,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn around_splits_at_antimeridian() {
+        let center = Point::new(0.0, 179.9);
+        let boxes = BBox::around(&center, 50_000.0);
+        assert_eq!(boxes.len(), 2);
+        let across = Point::new(0.0, -179.9);
+        assert!(across.within_radius(&center, 50_000.0));
+        assert!(boxes.iter().any(|x| x.contains(&across)));
+        assert!(boxes.iter().any(|x| x.contains(&center)));
+        assert_eq!(BBox::around(&Point::new(0.0, 0.0), 50_000.0).len(), 1);
+        assert_eq!(BBox::around(&Point::new(89.9, 0.0), 50_000.0).len(), 1);
+    }
+
+    #[test]
+    fn polygon_near_across_antimeridian() {
+        let polygon = Polygon::new(vec![
+            Point::new(-1.0, -179.9), Point::new(-1.0, -179.0), Point::new(1.0, -179.0), Point::new(1.0, -179.9),
+        ]);
+        assert!(polygon.within_radius(&Point::new(0.0, 179.95), 20_000.0));
+    }
+}
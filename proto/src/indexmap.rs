@@ -1,7 +1,15 @@
 use crate::prelude::ValTag;
 
+#[cfg(feature = "std")]
 pub use indexmap::{IndexSet, IndexMap};
 
+/// Without std there is no `RandomState`, maps hash with foldhash instead.
+#[cfg(not(feature = "std"))]
+pub type IndexMap<K, V> = indexmap::IndexMap<K, V, foldhash::fast::RandomState>;
+
+#[cfg(not(feature = "std"))]
+pub type IndexSet<V> = indexmap::IndexSet<V, foldhash::fast::RandomState>;
+
 pub type IndexSetTag<V> = ValTag<IndexSet<V>>;
 pub type IndexMapTag<K, V> = ValTag<IndexMap<K, V>>;
 
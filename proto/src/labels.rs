@@ -0,0 +1,238 @@
+use alloc::string::String;
+
+use crate::prelude::{Uuid, IndexMap, IndexSet};
+
+/// Canonical label of a tag plus aliases and per-locale display names.
+///
+/// Locales are free-form strings like `fr` or `zh-CN`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Label {
+    pub canonical: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub aliases: IndexSet<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub localized: IndexMap<String, String>,
+}
+
+/// Lookup key for labels, trimmed and lowercased.
+pub fn normalize(term: &str) -> String {
+    term.trim().to_lowercase()
+}
+
+impl Label {
+    pub fn new<S: Into<String>>(canonical: S) -> Self {
+        Self {
+            canonical: canonical.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_alias<S: Into<String>>(mut self, alias: S) -> Self {
+        self.aliases.insert(alias.into());
+        self
+    }
+
+    pub fn with_localized<L: Into<String>, S: Into<String>>(mut self, locale: L, name: S) -> Self {
+        self.localized.insert(locale.into(), name.into());
+        self
+    }
+
+    /// Name for `locale`, falling back to its language (`fr` for `fr-CA`) and then the canonical label.
+    pub fn display(&self, locale: &str) -> &str {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        self.localized.get(locale)
+            .or_else(|| self.localized.get(language))
+            .unwrap_or(&self.canonical)
+    }
+
+    /// Canonical label, aliases and localized names.
+    pub fn terms(&self) -> impl Iterator<Item = &str> {
+        core::iter::once(self.canonical.as_str())
+            .chain(self.aliases.iter().map(|x| x.as_str()))
+            .chain(self.localized.values().map(|x| x.as_str()))
+    }
+}
+
+/// Labels of tags, indexed by every term so any alias finds the tag.
+///
+/// Different tags may share a term, e.g. the same name under different parents,
+/// `lookup` only answers when it is unambiguous.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(from = "IndexMap<Uuid, Label>", into = "IndexMap<Uuid, Label>"))]
+pub struct Labels {
+    labels: IndexMap<Uuid, Label>,
+    index: IndexMap<String, IndexSet<Uuid>>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&Label> {
+        self.labels.get(uuid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &Label)> {
+        self.labels.iter()
+    }
+
+    /// Set the label of a tag, returning the previous one.
+    pub fn set(&mut self, uuid: Uuid, label: Label) -> Option<Label> {
+        let old = self.remove(&uuid);
+        for term in label.terms() {
+            self.index.entry(normalize(term)).or_default().insert(uuid);
+        }
+        self.labels.insert(uuid, label);
+        old
+    }
+
+    pub fn remove(&mut self, uuid: &Uuid) -> Option<Label> {
+        let label = self.labels.shift_remove(uuid)?;
+        for term in label.terms() {
+            let key = normalize(term);
+            if let Some(uuids) = self.index.get_mut(&key) {
+                uuids.shift_remove(uuid);
+                if uuids.is_empty() {
+                    self.index.shift_remove(&key);
+                }
+            }
+        }
+        Some(label)
+    }
+
+    /// All tags with `term` as label, alias or localized name.
+    pub fn lookup_all(&self, term: &str) -> impl Iterator<Item = &Uuid> {
+        self.index.get(&normalize(term))
+            .into_iter()
+            .flat_map(|x| x.iter())
+    }
+
+    /// The tag with `term` as any of its names, None if there is none or more than one.
+    pub fn lookup(&self, term: &str) -> Option<&Uuid> {
+        let mut found = self.lookup_all(term);
+        let first = found.next()?;
+        found.next().is_none().then_some(first)
+    }
+
+    /// Tags with a term starting with `prefix`, each once, best matches first.
+    ///
+    /// A tag ranks by its shortest matching term, so exact matches come first,
+    /// ties are sorted by term.
+    pub fn search(&self, prefix: &str) -> IndexSet<Uuid> {
+        let prefix = normalize(prefix);
+        let mut found: IndexMap<Uuid, &str> = IndexMap::default();
+        for (term, uuids) in self.index.iter().filter(|(term, _)| term.starts_with(&prefix)) {
+            for uuid in uuids.iter() {
+                let best = found.entry(*uuid).or_insert(term);
+                if (term.len(), term.as_str()) < (best.len(), *best) {
+                    *best = term;
+                }
+            }
+        }
+        found.sort_by(|_, a, _, b| (a.len(), a).cmp(&(b.len(), b)));
+        found.into_keys().collect()
+    }
+
+    pub fn display(&self, uuid: &Uuid, locale: &str) -> Option<&str> {
+        self.get(uuid).map(|x| x.display(locale))
+    }
+}
+
+impl From<IndexMap<Uuid, Label>> for Labels {
+    fn from(v: IndexMap<Uuid, Label>) -> Self {
+        let mut labels = Self::new();
+        for (uuid, label) in v.into_iter() {
+            labels.set(uuid, label);
+        }
+        labels
+    }
+}
+
+impl From<Labels> for IndexMap<Uuid, Label> {
+    fn from(v: Labels) -> Self {
+        v.labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn labels() -> Labels {
+        let mut labels = Labels::new();
+        labels.set(Uuid::from_u128(1), Label::new("Jazz").with_alias("Jazz Music").with_localized("fr", "Jazz (musique)").with_localized("zh-CN", "爵士乐"));
+        labels.set(Uuid::from_u128(2), Label::new("Jazz Fusion").with_alias("Fusion"));
+        labels.set(Uuid::from_u128(3), Label::new("Rock").with_localized("fr-CA", "Roc").with_localized("fr", "Rock (musique)"));
+        labels.set(Uuid::from_u128(4), Label::new("Acid Jazz").with_alias("jazz-funk"));
+        labels.set(Uuid::from_u128(5), Label::new("Fusion"));
+        labels
+    }
+
+    fn search(labels: &Labels, prefix: &str) -> Vec<u128> {
+        labels.search(prefix).into_iter().map(|x| x.as_u128()).collect()
+    }
+
+    #[test]
+    fn display_falls_back() {
+        let labels = labels();
+        let rock = Uuid::from_u128(3);
+        assert_eq!(labels.display(&rock, "fr-CA"), Some("Roc"));
+        assert_eq!(labels.display(&rock, "fr-BE"), Some("Rock (musique)"));
+        assert_eq!(labels.display(&rock, "fr_CH"), Some("Rock (musique)"));
+        assert_eq!(labels.display(&rock, "fr"), Some("Rock (musique)"));
+        assert_eq!(labels.display(&rock, "de"), Some("Rock"));
+        assert_eq!(labels.display(&Uuid::from_u128(1), "zh-CN"), Some("爵士乐"));
+        assert_eq!(labels.display(&Uuid::from_u128(1), "zh-TW"), Some("Jazz"));
+        assert_eq!(labels.display(&Uuid::from_u128(9), "fr"), None);
+    }
+
+    #[test]
+    fn lookup_any_term() {
+        let labels = labels();
+        assert_eq!(labels.lookup("  JAZZ music "), Some(&Uuid::from_u128(1)));
+        assert_eq!(labels.lookup("jazz (musique)"), Some(&Uuid::from_u128(1)));
+        assert_eq!(labels.lookup("roc"), Some(&Uuid::from_u128(3)));
+        // shared by two tags
+        assert_eq!(labels.lookup("fusion"), None);
+        assert_eq!(labels.lookup_all("fusion").count(), 2);
+        assert_eq!(labels.lookup("blues"), None);
+    }
+
+    #[test]
+    fn set_replaces_terms() {
+        let mut labels = labels();
+        let old = labels.set(Uuid::from_u128(5), Label::new("Blues"));
+        assert_eq!(old.map(|x| x.canonical), Some("Fusion".into()));
+        assert_eq!(labels.lookup("fusion"), Some(&Uuid::from_u128(2)));
+        assert_eq!(labels.lookup("blues"), Some(&Uuid::from_u128(5)));
+        labels.remove(&Uuid::from_u128(5));
+        assert_eq!(labels.lookup("blues"), None);
+        assert_eq!(labels.len(), 4);
+    }
+
+    #[test]
+    fn search_ranks_matches() {
+        let labels = labels();
+        // exact first, then shorter terms, then by term
+        assert_eq!(search(&labels, "jazz"), [1, 4, 2]);
+        assert_eq!(search(&labels, "Jazz F"), [2]);
+        assert_eq!(search(&labels, "fu"), [2, 5]);
+        assert_eq!(search(&labels, "ro"), [3]);
+        assert_eq!(search(&labels, "acid jazz"), [4]);
+        assert!(search(&labels, "jazzy").is_empty());
+        assert_eq!(search(&labels, "").len(), 5);
+    }
+}
@@ -1,15 +1,36 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 
 pub use tag_core;
 
+#[cfg(feature = "temporal")]
+pub use chrono;
+
 pub mod tag;
+pub mod meta;
 
 pub mod val;
 pub mod vec;
+#[cfg(feature = "std")]
 pub mod map;
 
 pub mod indexmap;
 
+pub mod dynamic;
+
 pub mod tags;
+pub mod validate;
+pub mod names;
+pub mod labels;
+pub mod crdt;
+pub mod query;
+#[cfg(feature = "std")]
+pub mod geo;
+
+#[cfg(feature = "temporal")]
+pub mod temporal;
 
 #[cfg(feature = "serde")]
 pub mod serde;
@@ -22,12 +43,20 @@ pub mod prelude {
     #[doc(hidden)]
     pub use crate::tag::Tag;
 
+    #[doc(hidden)]
+    pub use crate::meta::{TagMeta, Timestamp};
+
+    #[cfg(feature = "derive")]
+    #[doc(hidden)]
+    pub use tag_derive::Tag;
+
     #[doc(hidden)]
     pub use crate::val::ValTag;
 
     #[doc(hidden)]
     pub use crate::vec::VecTag;
 
+    #[cfg(feature = "std")]
     #[doc(hidden)]
     pub use crate::map::{SetTag, MapTag};
 
@@ -35,9 +64,51 @@ pub mod prelude {
     pub use crate::indexmap::{IndexSet, IndexMap, IndexSetTag, IndexMapTag};
 
     #[doc(hidden)]
-    pub use crate::tags::Tags;
+    pub use crate::dynamic::{Value, DynTag, Bytes, ValueError};
+
+    #[doc(hidden)]
+    pub use crate::tags::{Tags, TagsError, TagsResult};
+
+    #[doc(hidden)]
+    pub use crate::validate::{validate, Violation, ValidationReport};
+
+    #[doc(hidden)]
+    pub use crate::names::{TagNames, PathError, PathResult};
+
+    #[doc(hidden)]
+    pub use crate::labels::{Label, Labels};
+
+    #[doc(hidden)]
+    pub use crate::crdt::{CrdtTags, CrdtEntry, LwwRegister, Stamp};
+
+    #[doc(hidden)]
+    pub use crate::query::{QueryValue, QueryTag};
+
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    pub use crate::geo::{Point, BBox, Polygon, Geometry, PointTag, PolygonTag, GeometryTag, Spatial, SpatialTag};
+
+    #[cfg(feature = "temporal")]
+    #[doc(hidden)]
+    pub use crate::temporal::{IsoDuration, DateTag, DateTimeTag, DurationTag, Temporal, TemporalTag, Bucket, TemporalError};
 
     #[cfg(feature = "serde")]
     #[doc(hidden)]
     pub use crate::serde::*;
+
+    #[cfg(feature = "serde")]
+    #[doc(hidden)]
+    pub use crate::serde::value::SerdeValue;
+
+    #[cfg(feature = "serde")]
+    #[doc(hidden)]
+    pub use crate::serde::migration::SerdeMigration;
+
+    #[cfg(feature = "serde")]
+    #[doc(hidden)]
+    pub use crate::serde::patch::{TagPatch, TagUpdate, PatchError};
+
+    #[cfg(feature = "taxonomy")]
+    #[doc(hidden)]
+    pub use crate::serde::taxonomy::{Taxonomy, TaxonomyNode, TaxonomyError};
 }
@@ -0,0 +1,90 @@
+use alloc::string::String;
+
+/// Milliseconds since the unix epoch.
+pub type Timestamp = u64;
+
+#[cfg(feature = "std")]
+pub fn now() -> Timestamp {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as Timestamp)
+        .unwrap_or(0)
+}
+
+/// Optional provenance of a tag, `author` and `source` describe who and which
+/// import introduced it.
+///
+/// Human-readable formats leave out unset fields, others write all of them so
+/// non self-describing formats can read them back. Missing fields read as None.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TagMeta {
+    pub created: Option<Timestamp>,
+    pub modified: Option<Timestamp>,
+    pub author: Option<String>,
+    pub source: Option<String>,
+}
+
+impl TagMeta {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn with_author<S: Into<String>>(mut self, author: S) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Record an edit at `at`, `old` is the metadata of the tag being replaced.
+    ///
+    /// A new tag gets `created` if unset, a replacing one keeps the creation
+    /// fields of `old` it doesn't set itself and gets `modified`.
+    pub fn stamp(&mut self, old: Option<&TagMeta>, at: Timestamp) {
+        match old {
+            Some(old) => {
+                self.created = self.created.or(old.created);
+                if self.author.is_none() {
+                    self.author.clone_from(&old.author);
+                }
+                if self.source.is_none() {
+                    self.source.clone_from(&old.source);
+                }
+                self.modified = Some(at);
+            },
+            None => {
+                self.created.get_or_insert(at);
+            },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TagMeta {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let all = !serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("TagMeta", 4)?;
+        macro_rules! field {
+            ($name: ident) => {
+                if all || self.$name.is_some() {
+                    state.serialize_field(stringify!($name), &self.$name)?;
+                } else {
+                    state.skip_field(stringify!($name))?;
+                }
+            }
+        }
+        field!(created);
+        field!(modified);
+        field!(author);
+        field!(source);
+        state.end()
+    }
+}
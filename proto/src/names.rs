@@ -0,0 +1,317 @@
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, Tag, Tags, IndexMap, Labels, Timestamp};
+
+#[cfg(feature = "std")]
+use crate::meta::now;
+
+pub use tag_core::identity::{PATH_SEPARATOR as SEPARATOR, split_path};
+
+#[derive(Debug, Snafu)]
+pub enum PathError {
+    #[snafu(display("Invalid name: `{}`", name))]
+    InvalidName { name: String },
+    #[snafu(display("Tag not found: `{}`", uuid))]
+    TagNotFound { uuid: Uuid },
+    #[snafu(display("Name taken: `{}` -> `{}`", name, uuid))]
+    NameTaken { name: String, uuid: Uuid },
+    #[snafu(display("Created tag mismatch: `{}` -> `{}`", name, uuid))]
+    CreatedTagMismatch { name: String, uuid: Uuid },
+}
+
+pub type PathResult<T> = core::result::Result<T, PathError>;
+
+/// Human-readable names for tags in a `Tags`, unique among siblings.
+///
+/// Names are indexed under the parent the tag had when named, call `reindex`
+/// after re-parenting tags.
+#[derive(Debug, Clone, Default)]
+pub struct TagNames {
+    // the name with the parent it is indexed under
+    names: IndexMap<Uuid, (Option<Uuid>, String)>,
+    index: IndexMap<(Option<Uuid>, String), Uuid>,
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(SEPARATOR) && name.trim() == name
+}
+
+impl TagNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name_of(&self, uuid: &Uuid) -> Option<&str> {
+        self.names.get(uuid).map(|(_, name)| name.as_str())
+    }
+
+    pub fn child_named(&self, parent: Option<&Uuid>, name: &str) -> Option<&Uuid> {
+        self.index.get(&(parent.copied(), name.to_owned()))
+    }
+
+    pub fn set_name<T: ?Sized + Tag>(&mut self, tags: &Tags<T>, uuid: &Uuid, name: &str) -> PathResult<()> {
+        ensure!(is_valid_name(name), InvalidNameSnafu { name });
+        let tag = tags.get(uuid).context(TagNotFoundSnafu { uuid: *uuid })?;
+        let key = (tag.parent().copied(), name.to_owned());
+        if let Some(other) = self.index.get(&key) {
+            ensure!(other == uuid, NameTakenSnafu { name, uuid: *other });
+            return Ok(());
+        }
+        self.remove_name(uuid);
+        self.index.insert(key.clone(), *uuid);
+        self.names.insert(*uuid, key);
+        Ok(())
+    }
+
+    pub fn remove_name(&mut self, uuid: &Uuid) -> Option<String> {
+        let key = self.names.swap_remove(uuid)?;
+        self.index.swap_remove(&key);
+        Some(key.1)
+    }
+
+    /// Rebuild the sibling index from current parents, dropping names of removed tags.
+    ///
+    /// Tags that kept their parent keep their names. Returns the uuids of moved
+    /// tags whose names now clash with a sibling, they are unnamed.
+    pub fn reindex<T: ?Sized + Tag>(&mut self, tags: &Tags<T>) -> Vec<Uuid> {
+        self.names.retain(|uuid, _| tags.contains(uuid));
+        self.index.clear();
+        let mut moved = Vec::new();
+        for (uuid, key) in self.names.iter_mut() {
+            let parent = tags.get(uuid).and_then(|x| x.parent()).copied();
+            if key.0 == parent {
+                self.index.insert(key.clone(), *uuid);
+            } else {
+                key.0 = parent;
+                moved.push(*uuid);
+            }
+        }
+        let mut clashes = Vec::new();
+        for uuid in moved {
+            let Some(key) = self.names.get(&uuid) else {
+                continue;
+            };
+            if self.index.contains_key(key) {
+                clashes.push(uuid);
+            } else {
+                self.index.insert(key.clone(), uuid);
+            }
+        }
+        for uuid in clashes.iter() {
+            self.names.swap_remove(uuid);
+        }
+        clashes
+    }
+
+    pub fn resolve<T: ?Sized + Tag>(&self, tags: &Tags<T>, path: &str) -> Option<Uuid> {
+        let mut current: Option<Uuid> = None;
+        for name in split_path(path) {
+            let uuid = *self.child_named(current.as_ref(), name)?;
+            let tag = tags.get(&uuid)?;
+            if tag.parent() != current.as_ref() {
+                return None;
+            }
+            current = Some(uuid);
+        }
+        current
+    }
+
+    /// Like `resolve`, but a segment that isn't a child's name may also be a
+    /// child's label, alias or localized name, as long as only one child has it.
+    pub fn resolve_with_labels<T: ?Sized + Tag>(&self, tags: &Tags<T>, labels: &Labels, path: &str) -> Option<Uuid> {
+        let mut current: Option<Uuid> = None;
+        for name in split_path(path) {
+            let is_child = |uuid: &Uuid| {
+                tags.get(uuid).map(|x| x.parent() == current.as_ref()).unwrap_or(false)
+            };
+            let uuid = match self.child_named(current.as_ref(), name).filter(|x| is_child(x)) {
+                Some(uuid) => *uuid,
+                None => {
+                    let mut found = labels.lookup_all(name).filter(|x| is_child(x));
+                    let uuid = *found.next()?;
+                    if found.next().is_some() {
+                        return None;
+                    }
+                    uuid
+                },
+            };
+            current = Some(uuid);
+        }
+        current
+    }
+
+    /// Resolve a path, creating tags for missing segments.
+    ///
+    /// `create` gets the parent and the new tag's name, the tag it returns must
+    /// have that parent and a uuid not in `tags` yet.
+    #[cfg(feature = "std")]
+    pub fn resolve_or_create<T, F>(&mut self, tags: &mut Tags<T>, path: &str, create: F) -> PathResult<Uuid>
+        where
+            T: ?Sized + Tag,
+            F: FnMut(Option<&Uuid>, &str) -> Box<T>,
+    {
+        self.resolve_or_create_at(tags, path, now(), create)
+    }
+
+    pub fn resolve_or_create_at<T, F>(&mut self, tags: &mut Tags<T>, path: &str, at: Timestamp, mut create: F) -> PathResult<Uuid>
+        where
+            T: ?Sized + Tag,
+            F: FnMut(Option<&Uuid>, &str) -> Box<T>,
+    {
+        let mut current: Option<Uuid> = None;
+        for name in split_path(path) {
+            ensure!(is_valid_name(name), InvalidNameSnafu { name });
+            let existing = self.child_named(current.as_ref(), name)
+                .filter(|x| tags.get(x).map(|x| x.parent() == current.as_ref()).unwrap_or(false))
+                .copied();
+            let uuid = match existing {
+                Some(uuid) => uuid,
+                None => {
+                    let tag = create(current.as_ref(), name);
+                    let uuid = *tag.uuid();
+                    ensure!(tag.parent() == current.as_ref() && !tags.contains(&uuid),
+                        CreatedTagMismatchSnafu { name, uuid });
+                    tags.upsert_at(tag, at);
+                    self.set_name(tags, &uuid, name)?;
+                    uuid
+                },
+            };
+            current = Some(uuid);
+        }
+        current.context(InvalidNameSnafu { name: path })
+    }
+
+    /// Full path of a tag, None if it or any ancestor is unnamed.
+    pub fn path_of<T: ?Sized + Tag>(&self, tags: &Tags<T>, uuid: &Uuid) -> Option<String> {
+        let tag = tags.get(uuid)?;
+        let mut segments = vec![self.name_of(uuid)?];
+        for ancestor in tags.ancestors(uuid) {
+            segments.push(self.name_of(ancestor.uuid())?);
+        }
+        // a parent chain that doesn't end at a root is either dangling or a cycle
+        let root = tags.ancestors(uuid).last().unwrap_or(tag);
+        if root.parent().is_some() {
+            return None;
+        }
+        segments.reverse();
+        Some(segments.join(&SEPARATOR.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::prelude::{ValTag, TagMeta};
+
+    fn tag(uuid: u128, parent: Option<u128>) -> Box<ValTag<String>> {
+        Box::new(ValTag { uuid: Uuid::from_u128(uuid), parent: parent.map(Uuid::from_u128), val: uuid.to_string(), meta: TagMeta::default() })
+    }
+
+    fn named() -> (Tags<ValTag<String>>, TagNames) {
+        let tags = Tags::from_iter([tag(1, None), tag(2, Some(1)), tag(3, Some(2)), tag(4, Some(1))]);
+        let mut names = TagNames::new();
+        for (uuid, name) in [(1, "music"), (2, "jazz"), (3, "bebop"), (4, "rock")] {
+            names.set_name(&tags, &Uuid::from_u128(uuid), name).unwrap();
+        }
+        (tags, names)
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let (tags, names) = named();
+        assert_eq!(names.resolve(&tags, "music/jazz/bebop"), Some(Uuid::from_u128(3)));
+        assert_eq!(names.resolve(&tags, "music/rock"), Some(Uuid::from_u128(4)));
+        assert_eq!(names.resolve(&tags, "music/bebop"), None);
+        assert_eq!(names.resolve(&tags, "jazz"), None);
+        assert_eq!(names.path_of(&tags, &Uuid::from_u128(3)).as_deref(), Some("music/jazz/bebop"));
+        assert_eq!(names.path_of(&tags, &Uuid::from_u128(9)), None);
+    }
+
+    #[test]
+    fn path_of_needs_named_ancestors() {
+        let (mut tags, mut names) = named();
+        tags.insert(tag(5, Some(9)));
+        names.set_name(&tags, &Uuid::from_u128(5), "orphan").unwrap();
+        assert_eq!(names.path_of(&tags, &Uuid::from_u128(5)), None);
+        assert_eq!(names.remove_name(&Uuid::from_u128(2)).as_deref(), Some("jazz"));
+        assert_eq!(names.path_of(&tags, &Uuid::from_u128(3)), None);
+    }
+
+    #[test]
+    fn remove_name_frees_it() {
+        let (tags, mut names) = named();
+        assert_eq!(names.remove_name(&Uuid::from_u128(4)).as_deref(), Some("rock"));
+        assert_eq!(names.remove_name(&Uuid::from_u128(4)), None);
+        assert_eq!(names.name_of(&Uuid::from_u128(4)), None);
+        assert_eq!(names.resolve(&tags, "music/rock"), None);
+        assert_eq!(names.len(), 3);
+        names.set_name(&tags, &Uuid::from_u128(2), "rock").unwrap();
+        assert_eq!(names.resolve(&tags, "music/rock"), Some(Uuid::from_u128(2)));
+        assert_eq!(names.resolve(&tags, "music/jazz"), None);
+        assert!(matches!(names.set_name(&tags, &Uuid::from_u128(4), "rock"), Err(PathError::NameTaken { .. })));
+    }
+
+    #[test]
+    fn reindex_unnames_moved_clashes() {
+        let (mut tags, mut names) = named();
+        names.set_name(&tags, &Uuid::from_u128(3), "rock").unwrap();
+        tags.update_at(&Uuid::from_u128(3), 0, |x| x.parent = Some(Uuid::from_u128(1))).unwrap();
+        assert_eq!(names.reindex(&tags), [Uuid::from_u128(3)]);
+        assert_eq!(names.resolve(&tags, "music/rock"), Some(Uuid::from_u128(4)));
+        assert_eq!(names.name_of(&Uuid::from_u128(3)), None);
+    }
+
+    #[test]
+    fn resolve_or_create_at_reuses_existing() {
+        let (mut tags, mut names) = named();
+        let mut next = 10;
+        let mut create = |parent: Option<&Uuid>, _: &str| {
+            next += 1;
+            Box::new(ValTag { uuid: Uuid::from_u128(next), parent: parent.copied(), val: String::new(), meta: TagMeta::default() })
+        };
+        let uuid = names.resolve_or_create_at(&mut tags, "music/jazz/cool/west", 5, &mut create).unwrap();
+        assert_eq!(uuid, Uuid::from_u128(12));
+        assert_eq!(names.resolve(&tags, "music/jazz/cool"), Some(Uuid::from_u128(11)));
+        assert_eq!(tags.get(&uuid).map(|x| x.meta.created), Some(Some(5)));
+        assert_eq!(names.resolve_or_create_at(&mut tags, "music/jazz/cool", 6, &mut create).unwrap(), Uuid::from_u128(11));
+        assert_eq!(tags.len(), 6);
+        assert!(matches!(names.resolve_or_create_at(&mut tags, "music/ bad", 7, &mut create), Err(PathError::InvalidName { .. })));
+    }
+
+    #[test]
+    fn created_tags_must_fit() {
+        let (mut tags, mut names) = named();
+        let result = names.resolve_or_create_at(&mut tags, "music/new", 0, |_, _| tag(20, None));
+        assert!(matches!(result, Err(PathError::CreatedTagMismatch { .. })));
+        let result = names.resolve_or_create_at(&mut tags, "music/new", 0, |_, _| tag(2, Some(1)));
+        assert!(matches!(result, Err(PathError::CreatedTagMismatch { .. })));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn resolve_or_create_stamps_now() {
+        let mut tags: Tags<ValTag<String>> = Tags::new();
+        let mut names = TagNames::new();
+        let uuid = names.resolve_or_create(&mut tags, "a/b", |parent, name| {
+            Box::new(ValTag { uuid: Uuid::from_u128(name.len() as u128 + parent.map(|_| 1).unwrap_or(0)), parent: parent.copied(), val: name.to_string(), meta: TagMeta::default() })
+        }).unwrap();
+        assert_eq!(names.path_of(&tags, &uuid).as_deref(), Some("a/b"));
+        assert!(tags.get(&uuid).and_then(|x| x.meta.created).is_some());
+    }
+}
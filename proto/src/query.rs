@@ -0,0 +1,393 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Debug;
+use core::hash::{Hash, Hasher};
+
+#[cfg(feature = "std")]
+use std::collections::{HashSet, HashMap};
+
+use crate::prelude::{Uuid, Tag, ValTag, Value, ValueError, IndexSet, IndexSetTag, IndexMap};
+
+#[cfg(feature = "std")]
+use crate::prelude::SetTag;
+
+/// Uniform membership view over tag values, with members as dynamic `Value`s.
+///
+/// Sets and lists have their elements as members, maps have their keys and
+/// support `lookup`, other values are their only member.
+pub trait QueryValue {
+    fn members(&self) -> Vec<Value>;
+    fn contains_member(&self, member: &Value) -> bool;
+
+    fn lookup(&self, _key: &Value) -> Option<Value> {
+        None
+    }
+
+    fn any_of(&self, members: &[Value]) -> bool {
+        members.iter().any(|x| self.contains_member(x))
+    }
+
+    fn all_of(&self, members: &[Value]) -> bool {
+        members.iter().all(|x| self.contains_member(x))
+    }
+}
+
+pub trait QueryTag : Tag {
+    fn query(&self) -> &dyn QueryValue;
+}
+
+impl<V: QueryValue + Debug + 'static> QueryTag for ValTag<V> {
+    fn query(&self) -> &dyn QueryValue {
+        &self.val
+    }
+}
+
+macro_rules! downcast_query_value {
+    ($val: ident; $($value_type: ty),* $(,)?) => {
+        $(
+            if let Some(x) = $val.downcast_ref::<$value_type>() {
+                return Some(x);
+            }
+        )*
+    };
+}
+
+macro_rules! downcast_query_maps {
+    ($val: ident; [$($key_type: ty),* $(,)?]; $value_types: tt) => {
+        $(
+            downcast_query_maps!(@key $val; $key_type; $value_types);
+        )*
+    };
+    (@key $val: ident; $key_type: ty; [$($value_type: ty),* $(,)?]) => {
+        downcast_query_value!($val; $(IndexMap<$key_type, $value_type>),*);
+        #[cfg(feature = "std")]
+        downcast_query_value!($val; $(HashMap<$key_type, $value_type>),*);
+    };
+}
+
+/// A value as a `QueryValue` if its type is one of the scalars, lists, sets
+/// or maps of them that implement it, for `Tag::as_query` of generic tags.
+pub fn query_value<V: Any>(val: &V) -> Option<&dyn QueryValue> {
+    let val = val as &dyn Any;
+    downcast_query_value!(val;
+        Value,
+        bool, u8, i8, u16, i16, u32, i32, i64, f32, f64, String, Uuid,
+        Vec<bool>, Vec<u8>, Vec<i8>, Vec<u16>, Vec<i16>, Vec<u32>, Vec<i32>, Vec<i64>,
+        Vec<f32>, Vec<f64>, Vec<String>, Vec<Uuid>,
+        IndexSet<bool>, IndexSet<u8>, IndexSet<i8>, IndexSet<u16>, IndexSet<i16>,
+        IndexSet<u32>, IndexSet<i32>, IndexSet<i64>, IndexSet<String>, IndexSet<Uuid>,
+    );
+    #[cfg(feature = "std")]
+    downcast_query_value!(val;
+        HashSet<bool>, HashSet<u8>, HashSet<i8>, HashSet<u16>, HashSet<i16>,
+        HashSet<u32>, HashSet<i32>, HashSet<i64>, HashSet<String>, HashSet<Uuid>,
+    );
+    downcast_query_maps!(val;
+        [bool, u8, i8, u16, i16, u32, i32, i64, String, Uuid];
+        [bool, i64, f64, String, Value]
+    );
+    None
+}
+
+/// Query values of the tags that have one, see `Tag::as_query`.
+pub fn queries<'a, T, I>(tags: I) -> impl Iterator<Item = &'a dyn QueryValue>
+    where
+        T: Tag + ?Sized + 'a,
+        I: IntoIterator<Item = &'a T>,
+{
+    tags.into_iter().filter_map(|x| x.as_query())
+}
+
+// members compared like `Value`, hashed so that equal values hash the same
+struct Member(Value);
+
+impl PartialEq for Member {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Member {}
+
+impl Hash for Member {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(&self.0, state);
+    }
+}
+
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    core::mem::discriminant(value).hash(state);
+    match value {
+        Value::Null => {},
+        Value::Bool(v) => v.hash(state),
+        Value::Int(v) => v.hash(state),
+        // 0.0 and -0.0 are equal
+        Value::Float(v) => (if *v == 0.0 { 0.0f64 } else { *v }).to_bits().hash(state),
+        Value::String(v) => v.hash(state),
+        Value::Bytes(v) => v.hash(state),
+        Value::List(v) => {
+            v.len().hash(state);
+            for x in v {
+                hash_value(x, state);
+            }
+        },
+        // maps are equal in any order
+        Value::Map(v) => v.len().hash(state),
+        Value::Uuid(v) => v.hash(state),
+    }
+}
+
+fn members<'a, I>(values: I) -> IndexSet<Member>
+    where I: IntoIterator<Item = &'a dyn QueryValue>
+{
+    values.into_iter()
+        .flat_map(|x| x.members())
+        .map(Member)
+        .collect()
+}
+
+/// Members in any of the values, in first seen order.
+pub fn union<'a, I>(values: I) -> Vec<Value>
+    where I: IntoIterator<Item = &'a dyn QueryValue>
+{
+    members(values).into_iter().map(|x| x.0).collect()
+}
+
+/// Members in all of the values, empty if there are none.
+pub fn intersection<'a, I>(values: I) -> Vec<Value>
+    where I: IntoIterator<Item = &'a dyn QueryValue>
+{
+    let mut values = values.into_iter();
+    let Some(first) = values.next() else {
+        return Vec::new();
+    };
+    let mut result = members([first]);
+    for value in values {
+        result.retain(|x| value.contains_member(&x.0));
+    }
+    result.into_iter().map(|x| x.0).collect()
+}
+
+pub fn difference(value: &dyn QueryValue, other: &dyn QueryValue) -> Vec<Value> {
+    let mut result = members([value]);
+    result.retain(|x| !other.contains_member(&x.0));
+    result.into_iter().map(|x| x.0).collect()
+}
+
+macro_rules! impl_scalar_query_value {
+    ($($value_type: ty),* $(,)?) => {
+        $(
+            impl QueryValue for $value_type {
+                fn members(&self) -> Vec<Value> {
+                    vec![self.clone().into()]
+                }
+
+                fn contains_member(&self, member: &Value) -> bool {
+                    <$value_type>::try_from(member.clone())
+                        .map(|x| x == *self)
+                        .unwrap_or(false)
+                }
+            }
+        )*
+    }
+}
+
+impl_scalar_query_value!(
+    bool,
+    u8, i8, u16, i16, u32, i32, i64,
+    f32, f64,
+    String,
+    Uuid,
+);
+
+impl QueryValue for Value {
+    fn members(&self) -> Vec<Value> {
+        match self {
+            Value::List(v) => v.clone(),
+            Value::Map(v) => v.keys().cloned().map(Value::String).collect(),
+            other => vec![other.clone()],
+        }
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        match (self, member) {
+            (Value::List(v), member) => v.contains(member),
+            (Value::Map(v), Value::String(key)) => v.contains_key(key),
+            (Value::Map(_), _) => false,
+            (other, member) => other == member,
+        }
+    }
+
+    fn lookup(&self, key: &Value) -> Option<Value> {
+        match (self, key) {
+            (Value::Map(v), Value::String(key)) => v.get(key).cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl<V> QueryValue for Vec<V>
+    where V: Clone + PartialEq + Into<Value> + TryFrom<Value, Error = ValueError>
+{
+    fn members(&self) -> Vec<Value> {
+        self.iter().cloned().map(Into::into).collect()
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        V::try_from(member.clone())
+            .map(|x| self.contains(&x))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> QueryValue for HashSet<V>
+    where V: Clone + Eq + Hash + Into<Value> + TryFrom<Value, Error = ValueError>
+{
+    fn members(&self) -> Vec<Value> {
+        self.iter().cloned().map(Into::into).collect()
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        V::try_from(member.clone())
+            .map(|x| self.contains(&x))
+            .unwrap_or(false)
+    }
+}
+
+impl<V> QueryValue for IndexSet<V>
+    where V: Clone + Eq + Hash + Into<Value> + TryFrom<Value, Error = ValueError>
+{
+    fn members(&self) -> Vec<Value> {
+        self.iter().cloned().map(Into::into).collect()
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        V::try_from(member.clone())
+            .map(|x| self.contains(&x))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> QueryValue for HashMap<K, V>
+    where
+        K: Clone + Eq + Hash + Into<Value> + TryFrom<Value, Error = ValueError>,
+        V: Clone + Into<Value>,
+{
+    fn members(&self) -> Vec<Value> {
+        self.keys().cloned().map(Into::into).collect()
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        K::try_from(member.clone())
+            .map(|x| self.contains_key(&x))
+            .unwrap_or(false)
+    }
+
+    fn lookup(&self, key: &Value) -> Option<Value> {
+        let key = K::try_from(key.clone()).ok()?;
+        self.get(&key).cloned().map(Into::into)
+    }
+}
+
+impl<K, V> QueryValue for IndexMap<K, V>
+    where
+        K: Clone + Eq + Hash + Into<Value> + TryFrom<Value, Error = ValueError>,
+        V: Clone + Into<Value>,
+{
+    fn members(&self) -> Vec<Value> {
+        self.keys().cloned().map(Into::into).collect()
+    }
+
+    fn contains_member(&self, member: &Value) -> bool {
+        K::try_from(member.clone())
+            .map(|x| self.contains_key(&x))
+            .unwrap_or(false)
+    }
+
+    fn lookup(&self, key: &Value) -> Option<Value> {
+        let key = K::try_from(key.clone()).ok()?;
+        self.get(&key).cloned().map(Into::into)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Clone + Eq + Hash> SetTag<V> {
+    pub fn union(&self, other: &Self) -> HashSet<V> {
+        self.val.union(&other.val).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> HashSet<V> {
+        self.val.intersection(&other.val).cloned().collect()
+    }
+
+    pub fn difference(&self, other: &Self) -> HashSet<V> {
+        self.val.difference(&other.val).cloned().collect()
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.val.is_subset(&other.val)
+    }
+}
+
+impl<V: Clone + Eq + Hash> IndexSetTag<V> {
+    pub fn union(&self, other: &Self) -> IndexSet<V> {
+        self.val.union(&other.val).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> IndexSet<V> {
+        self.val.intersection(&other.val).cloned().collect()
+    }
+
+    pub fn difference(&self, other: &Self) -> IndexSet<V> {
+        self.val.difference(&other.val).cloned().collect()
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.val.is_subset(&other.val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+
+    fn tag<V>(uuid: u128, val: V) -> ValTag<V> {
+        ValTag { uuid: Uuid::from_u128(uuid), parent: None, val, meta: Default::default() }
+    }
+
+    #[test]
+    fn dyn_tags_can_be_queried() {
+        let tags: Vec<Box<dyn Tag>> = vec![
+            Box::new(tag(1, IndexSet::<i32>::from_iter([1, 2, 3]))),
+            Box::new(tag(2, vec![2i64, 3, 4, 4])),
+            Box::new(tag(3, Value::List(vec![Value::Int(3), Value::Float(0.5)]))),
+            Box::new(tag(4, 7u64)),
+        ];
+        assert!(tags[0].as_query().is_some_and(|x| x.contains_member(&Value::Int(2))));
+        assert!(tags[3].as_query().is_none());
+        assert_eq!(queries(tags.iter().map(|x| x.as_ref())).count(), 3);
+        assert_eq!(intersection(queries(tags.iter().map(|x| x.as_ref()))), vec![Value::Int(3)]);
+        assert_eq!(union(queries(tags.iter().map(|x| x.as_ref()))), vec![
+            Value::Int(1), Value::Int(2), Value::Int(3), Value::Int(4), Value::Float(0.5),
+        ]);
+    }
+
+    #[test]
+    fn maps_can_be_queried() {
+        let map = tag(1, IndexMap::<String, i64>::from_iter([(String::from("k"), 5)]));
+        let query = (&map as &dyn Tag).as_query();
+        assert_eq!(query.and_then(|x| x.lookup(&Value::String(String::from("k")))), Some(Value::Int(5)));
+    }
+
+    #[test]
+    fn union_dedups_equal_floats() {
+        let value = Value::List(vec![Value::Float(0.0), Value::Float(-0.0), Value::Float(1.0)]);
+        assert_eq!(union([&value as &dyn QueryValue]), vec![Value::Float(0.0), Value::Float(1.0)]);
+    }
+}
@@ -0,0 +1,285 @@
+use bincode::Options;
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, IndexSet, TagMeta, SerdeTags};
+use crate::serde::registry::get_registration;
+
+// Layout, all integers are LEB128 varints unless noted:
+//
+//   magic `TAGS`, version as u16 little endian
+//   type names: count, then (length, utf8 bytes) each
+//   uuids: count, then 16 bytes each
+//   tags: count, then (type index, uuid index, parent index + 1 or 0, payload length, payload,
+//         meta length, meta) each
+//
+// Payloads are the bincode encoding of `SerdeTagType::payload()`, meta is the
+// bincode encoding of `TagMeta` with length 0 for none or empty. Version 1 has
+// no meta and can still be decoded.
+
+pub const MAGIC: &[u8; 4] = b"TAGS";
+pub const VERSION: u16 = 2;
+
+#[derive(Debug, Snafu)]
+pub enum BinaryError {
+    #[snafu(display("Invalid header"))]
+    InvalidHeader,
+    #[snafu(display("Unsupported version: `{}`", version))]
+    UnsupportedVersion { version: u16 },
+    #[snafu(display("Unexpected end of data at: `{}`", offset))]
+    UnexpectedEnd { offset: usize },
+    #[snafu(display("Invalid varint at: `{}`", offset))]
+    InvalidVarint { offset: usize },
+    #[snafu(display("Invalid utf8 at: `{}`", offset))]
+    InvalidUtf8 { offset: usize },
+    #[snafu(display("Invalid index: `{}` of `{}`", index, kind))]
+    InvalidIndex { index: usize, kind: &'static str },
+    #[snafu(display("Unknown tag type: {}", info))]
+    UnknownType { info: String },
+    #[snafu(display("Payload failed: `{}` -> {}", tag_type_name, info))]
+    PayloadFailed { info: String, tag_type_name: String },
+    #[snafu(display("Uuid mismatch: `{}` -> `{}`", expected, actual))]
+    UuidMismatch { expected: Uuid, actual: Uuid },
+}
+
+pub type BinaryResult<T> = std::result::Result<T, BinaryError>;
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+pub fn encode(tags: &SerdeTags) -> BinaryResult<Vec<u8>> {
+    let mut type_names = IndexSet::new();
+    let mut uuids = IndexSet::new();
+    let mut records = Vec::new();
+    for tag in tags.iter() {
+        let tag_type_name = tag.tag_type_name();
+        let payload = options().serialize(tag.payload())
+            .map_err(|x| BinaryError::PayloadFailed {
+                info: x.to_string(),
+                tag_type_name: tag_type_name.to_string(),
+            })?;
+        let meta = match tag.meta().filter(|x| !x.is_empty()) {
+            Some(meta) => options().serialize(meta)
+                .map_err(|x| BinaryError::PayloadFailed {
+                    info: x.to_string(),
+                    tag_type_name: tag_type_name.to_string(),
+                })?,
+            None => Vec::new(),
+        };
+        let (type_index, _) = type_names.insert_full(tag_type_name);
+        let (uuid_index, _) = uuids.insert_full(*tag.uuid());
+        let parent_index = tag.parent()
+            .map(|x| uuids.insert_full(*x).0 + 1)
+            .unwrap_or(0);
+        records.push((type_index, uuid_index, parent_index, payload, meta));
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    write_varint(&mut bytes, type_names.len());
+    for name in type_names.iter() {
+        write_varint(&mut bytes, name.len());
+        bytes.extend_from_slice(name.as_bytes());
+    }
+    write_varint(&mut bytes, uuids.len());
+    for uuid in uuids.iter() {
+        bytes.extend_from_slice(uuid.as_bytes());
+    }
+    write_varint(&mut bytes, records.len());
+    for (type_index, uuid_index, parent_index, payload, meta) in records.iter() {
+        write_varint(&mut bytes, *type_index);
+        write_varint(&mut bytes, *uuid_index);
+        write_varint(&mut bytes, *parent_index);
+        write_varint(&mut bytes, payload.len());
+        bytes.extend_from_slice(payload);
+        write_varint(&mut bytes, meta.len());
+        bytes.extend_from_slice(meta);
+    }
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> BinaryResult<SerdeTags> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return InvalidHeaderSnafu.fail();
+    }
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    ensure!(version == 1 || version == VERSION, UnsupportedVersionSnafu { version });
+
+    let mut registrations = Vec::new();
+    for _ in 0..reader.varint()? {
+        let len = reader.varint()?;
+        let offset = reader.offset;
+        let name = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| BinaryError::InvalidUtf8 { offset })?;
+        let registration = get_registration(name)
+            .map_err(|info| BinaryError::UnknownType { info })?;
+        registrations.push((name, registration));
+    }
+    let mut uuids = Vec::new();
+    for _ in 0..reader.varint()? {
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(reader.take(16)?);
+        uuids.push(Uuid::from_bytes(uuid));
+    }
+    let get_uuid = |index: usize| {
+        uuids.get(index).copied()
+            .context(InvalidIndexSnafu { index, kind: "uuid" })
+    };
+
+    let count = reader.varint()?;
+    let mut tags = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let type_index = reader.varint()?;
+        let (name, registration) = registrations.get(type_index)
+            .context(InvalidIndexSnafu { index: type_index, kind: "type" })?;
+        let uuid = get_uuid(reader.varint()?)?;
+        let parent = match reader.varint()? {
+            0 => None,
+            index => Some(get_uuid(index - 1)?),
+        };
+        let len = reader.varint()?;
+        let mut deserializer = bincode::Deserializer::from_slice(reader.take(len)?, options());
+        let mut erased = <dyn erased_serde::Deserializer>::erase(&mut deserializer);
+        let mut tag = (registration.from_payload)(uuid, parent, &mut erased)
+            .map_err(|x| BinaryError::PayloadFailed {
+                info: x.to_string(),
+                tag_type_name: name.to_string(),
+            })?;
+        let len = if version > 1 { reader.varint()? } else { 0 };
+        if len > 0 {
+            let meta: TagMeta = options().deserialize(reader.take(len)?)
+                .map_err(|x| BinaryError::PayloadFailed {
+                    info: x.to_string(),
+                    tag_type_name: name.to_string(),
+                })?;
+            if let Some(tag_meta) = tag.meta_mut() {
+                *tag_meta = meta;
+            }
+        }
+        ensure!(tag.uuid() == &uuid, UuidMismatchSnafu { expected: uuid, actual: *tag.uuid() });
+        tags.push(tag);
+    }
+    Ok(SerdeTags(tags))
+}
+
+impl SerdeTags {
+    pub fn to_binary(&self) -> BinaryResult<Vec<u8>> {
+        encode(self)
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> BinaryResult<Self> {
+        decode(bytes)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> BinaryResult<&'a [u8]> {
+        let end = self.offset.checked_add(len)
+            .filter(|x| *x <= self.bytes.len())
+            .context(UnexpectedEndSnafu { offset: self.offset })?;
+        let result = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(result)
+    }
+
+    fn byte(&mut self) -> BinaryResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> BinaryResult<usize> {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            ensure!(shift < usize::BITS, InvalidVarintSnafu { offset: self.offset });
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{ValTag, DynTag, Value, TagMeta};
+
+    fn tags() -> SerdeTags {
+        let root = ValTag {
+            uuid: Uuid::from_u128(1),
+            parent: None,
+            val: "root".to_string(),
+            meta: TagMeta { created: Some(5), ..Default::default() }.with_author("ann"),
+        };
+        let child = ValTag {
+            uuid: Uuid::from_u128(2),
+            parent: Some(Uuid::from_u128(1)),
+            val: 7u64,
+            meta: Default::default(),
+        };
+        let dynamic = DynTag {
+            uuid: Uuid::from_u128(3),
+            parent: Some(Uuid::from_u128(1)),
+            val: Value::List(vec![Value::Int(-1), Value::Bytes(vec![1, 2]), Value::Null]),
+            meta: Default::default(),
+        };
+        SerdeTags(vec![Box::new(root), Box::new(child), Box::new(dynamic)])
+    }
+
+    #[test]
+    fn round_trip() {
+        let tags = tags();
+        let decoded = decode(&encode(&tags).unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&tags).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        let mut bytes = encode(&tags()).unwrap();
+        bytes[0] = b'X';
+        assert!(matches!(decode(&bytes), Err(BinaryError::InvalidHeader)));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut bytes = encode(&tags()).unwrap();
+        bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(decode(&bytes), Err(BinaryError::UnsupportedVersion { version }) if version == VERSION + 1));
+    }
+
+    #[test]
+    fn decodes_version_1() {
+        // a single tag without meta, version 2 only adds the trailing meta length
+        let tags = SerdeTags(vec![tags().0.remove(1)]);
+        let mut bytes = encode(&tags).unwrap();
+        assert_eq!(bytes.pop(), Some(0));
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&tags).unwrap());
+    }
+
+    #[test]
+    fn truncated_input_fails() {
+        let bytes = encode(&tags()).unwrap();
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err(), "{}", len);
+        }
+    }
+}
@@ -1,62 +1,108 @@
-use crate::prelude::{SerdeTag, SetTag, IndexSetTag, MapTag, IndexMapTag};
-
-use crate::{impl_serde_kv_tag, impl_serde_v_tag};
-
-macro_rules! impl_serde_key_tags {
-    ($type: ident, $key_type: ident) => {
-        impl_serde_kv_tag!($type, $key_type, bool);
-        impl_serde_kv_tag!($type, $key_type, u8);
-        impl_serde_kv_tag!($type, $key_type, i8);
-        impl_serde_kv_tag!($type, $key_type, u16);
-        impl_serde_kv_tag!($type, $key_type, i16);
-        impl_serde_kv_tag!($type, $key_type, u32);
-        impl_serde_kv_tag!($type, $key_type, i32);
-        impl_serde_kv_tag!($type, $key_type, u64);
-        impl_serde_kv_tag!($type, $key_type, i64);
-        impl_serde_kv_tag!($type, $key_type, u128);
-        impl_serde_kv_tag!($type, $key_type, i128);
-        impl_serde_kv_tag!($type, $key_type, f32);
-        impl_serde_kv_tag!($type, $key_type, f64);
-        impl_serde_kv_tag!($type, $key_type, String);
+use alloc::string::String;
+
+use crate::prelude::{Uuid, IndexSetTag, IndexMapTag};
+
+#[cfg(feature = "std")]
+use crate::prelude::{SetTag, MapTag};
+
+use crate::register_serde_tag;
+
+macro_rules! register_set_tags {
+    ($($value_type: ty),* $(,)?) => {
+        register_serde_tag!($(IndexSetTag<$value_type>),*);
+        #[cfg(feature = "std")]
+        register_serde_tag!($(SetTag<$value_type>),*);
     }
 }
 
-macro_rules! impl_serde_map_tags {
-    ($type: ident) => {
-        impl_serde_key_tags!($type, bool);
-        impl_serde_key_tags!($type, u8);
-        impl_serde_key_tags!($type, i8);
-        impl_serde_key_tags!($type, u16);
-        impl_serde_key_tags!($type, i16);
-        impl_serde_key_tags!($type, u32);
-        impl_serde_key_tags!($type, i32);
-        impl_serde_key_tags!($type, u64);
-        impl_serde_key_tags!($type, i64);
-        impl_serde_key_tags!($type, u128);
-        impl_serde_key_tags!($type, i128);
-        impl_serde_key_tags!($type, String);
-    }
+macro_rules! register_map_tags {
+    ([$($key_type: ty),* $(,)?], $value_types: tt) => {
+        $(
+            register_map_tags!(@key $key_type, $value_types);
+        )*
+    };
+    (@key $key_type: ty, [$($value_type: ty),* $(,)?]) => {
+        register_serde_tag!($(IndexMapTag<$key_type, $value_type>),*);
+        #[cfg(feature = "std")]
+        register_serde_tag!($(MapTag<$key_type, $value_type>),*);
+    };
 }
 
-macro_rules! impl_serde_set_tags {
-    ($type: ident) => {
-        impl_serde_v_tag!($type, bool);
-        impl_serde_v_tag!($type, u8);
-        impl_serde_v_tag!($type, i8);
-        impl_serde_v_tag!($type, u16);
-        impl_serde_v_tag!($type, i16);
-        impl_serde_v_tag!($type, u32);
-        impl_serde_v_tag!($type, i32);
-        impl_serde_v_tag!($type, u64);
-        impl_serde_v_tag!($type, i64);
-        impl_serde_v_tag!($type, u128);
-        impl_serde_v_tag!($type, i128);
-        impl_serde_v_tag!($type, String);
+register_set_tags!(
+    bool,
+    u8, i8, u16, i16, u32, i32, u64, i64, u128, i128,
+    String,
+    Uuid,
+);
+
+// a core set of key and value types, other pairs can be registered downstream
+// with `register_serde_tag!`
+register_map_tags!([
+    u32, u64, i64,
+    String,
+    Uuid,
+], [
+    bool,
+    u32, u64, i64,
+    f64,
+    String,
+    Uuid,
+]);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::prelude::{Uuid, MapTag, IndexSet, IndexSetTag, SerdeTags};
+    use crate::serde::registry::is_registered;
+
+    #[test]
+    fn core_pairs_are_registered() {
+        for key in ["u32", "u64", "i64", "String", "Uuid"] {
+            for value in ["bool", "u32", "u64", "i64", "f64", "String", "Uuid"] {
+                assert!(is_registered(&format!("ValTag<Map<{}, {}>>", key, value)), "{} -> {}", key, value);
+                assert!(is_registered(&format!("ValTag<IndexMap<{}, {}>>", key, value)), "{} -> {}", key, value);
+            }
+        }
+        assert!(!is_registered("ValTag<Map<u8, i16>>"));
     }
-}
 
-impl_serde_set_tags!(SetTag);
-impl_serde_set_tags!(IndexSetTag);
+    #[test]
+    fn legacy_tags_load() {
+        let json = r#"[
+            {"type": "MapTag<String, u32>", "uuid": "00000000-0000-0000-0000-000000000001", "parent": null, "val": {"a": 1}},
+            {"type": "IndexSetTag<String>", "uuid": "00000000-0000-0000-0000-000000000002", "parent": "00000000-0000-0000-0000-000000000001", "val": ["b"]}
+        ]"#;
+        let loaded: SerdeTags = serde_json::from_str(json).unwrap();
+        let map = MapTag::<String, u32> {
+            uuid: Uuid::from_u128(1),
+            parent: None,
+            val: HashMap::from([("a".to_string(), 1)]),
+            meta: Default::default(),
+        };
+        let set = IndexSetTag::<String> {
+            uuid: Uuid::from_u128(2),
+            parent: Some(Uuid::from_u128(1)),
+            val: IndexSet::from_iter(["b".to_string()]),
+            meta: Default::default(),
+        };
+        assert_eq!(loaded, SerdeTags(vec![Box::new(map), Box::new(set)]));
+        let json = serde_json::to_string(&loaded).unwrap();
+        assert!(json.contains(r#""type":"ValTag<Map<String, u32>>","tag":"#), "{}", json);
+    }
 
-impl_serde_map_tags!(MapTag);
-impl_serde_map_tags!(IndexMapTag);
+    #[test]
+    fn integer_keys_load() {
+        let tag = MapTag::<u32, String> {
+            uuid: Uuid::from_u128(1),
+            parent: None,
+            val: HashMap::from([(7, "seven".to_string())]),
+            meta: Default::default(),
+        };
+        let tags = SerdeTags(vec![Box::new(tag)]);
+        let json = serde_json::to_string(&tags).unwrap();
+        assert!(json.contains("ValTag<Map<u32, String>>"));
+        let loaded: SerdeTags = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, tags);
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use core::any::TypeId;
+
+use crate::prelude::{Uuid, Tag, SerdeTag, SerdeTagType};
+use crate::serde::registry::{Registration, tag_to_serde, tag_into_shared};
+
+/// Upgrade tags stored under an older type name to a current type.
+///
+/// A registered migration takes over the old name from any tag type registered
+/// with it, e.g. migrating `ValTag<u16>` to `ValTag<u32>` makes every stored
+/// `ValTag<u16>` load as `ValTag<u32>`. Migrations are not chained, so `To`
+/// should be the current type.
+///
+/// Metadata of the old tag is kept if the migrated one doesn't set any.
+///
+/// Renamed or changed types can keep the old definition around as `From`, with a
+/// versioned name for the new one, e.g. `#[tag(serde = "Genre@2")]`.
+pub trait SerdeMigration : 'static {
+    type From: SerdeTagType;
+    type To: SerdeTagType + Send + Sync;
+
+    fn migrate(from: Self::From) -> Self::To;
+}
+
+pub struct Migration(pub Registration);
+
+impl Migration {
+    pub const fn of<M: SerdeMigration>() -> Self {
+        Self(Registration {
+            tag_type_name: <M::From as SerdeTagType>::tag_type_name,
+            deserialize: deserialize_migrated::<M>,
+            from_payload: migrated_from_payload::<M>,
+            type_id: TypeId::of::<M::To>,
+            to_serde: tag_to_serde::<M::To>,
+            into_shared: tag_into_shared::<M::To>,
+        })
+    }
+
+    pub fn from_type_name(&self) -> Cow<'static, str> {
+        (self.0.tag_type_name)()
+    }
+}
+
+fn migrate<M: SerdeMigration>(from: M::From) -> Box<dyn SerdeTag> {
+    let meta = from.meta().cloned();
+    let mut to = M::migrate(from);
+    if let (Some(meta), Some(to_meta)) = (meta, to.meta_mut()) {
+        if to_meta.is_empty() {
+            *to_meta = meta;
+        }
+    }
+    Box::new(to)
+}
+
+fn deserialize_migrated<M: SerdeMigration>(deserializer: &mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error> {
+    let from = erased_serde::deserialize::<M::From>(deserializer)?;
+    Ok(migrate::<M>(from))
+}
+
+fn migrated_from_payload<M: SerdeMigration>(uuid: Uuid, parent: Option<Uuid>, payload: &mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error> {
+    let from = M::From::from_payload(uuid, parent, payload)?;
+    Ok(migrate::<M>(from))
+}
+
+inventory::collect!(Migration);
+
+#[macro_export]
+macro_rules! register_serde_migration {
+    ($($migration: ty),* $(,)?) => {
+        $(
+            $crate::serde::registry::inventory::submit! {
+                $crate::serde::migration::Migration::of::<$migration>()
+            }
+        )*
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use crate::prelude::{ValTag, TagMeta, SerdeTags, SerdeValue};
+    use super::SerdeMigration;
+
+    // an old value type only stored files still have, migrations take over
+    // names globally so tests can't migrate a type others use
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[serde(transparent)]
+    struct Stars(u16);
+
+    impl SerdeValue for Stars {
+        fn value_type_name() -> Cow<'static, str> {
+            Cow::Borrowed("Stars")
+        }
+    }
+
+    struct Widen;
+
+    impl SerdeMigration for Widen {
+        type From = ValTag<Stars>;
+        type To = ValTag<u32>;
+
+        fn migrate(from: ValTag<Stars>) -> ValTag<u32> {
+            ValTag { uuid: from.uuid, parent: from.parent, val: u32::from(from.val.0) * 1000, meta: TagMeta::default() }
+        }
+    }
+
+    crate::register_serde_migration!(Widen);
+
+    #[test]
+    fn stored_tags_are_migrated() {
+        let json = r#"[{"type": "ValTag<Stars>", "tag": {
+            "uuid": "00000000-0000-0000-0000-000000000001",
+            "parent": null,
+            "val": 7,
+            "meta": {"author": "ann"}
+        }}]"#;
+        let loaded: SerdeTags = serde_json::from_str(json).unwrap();
+        let tag = loaded.0[0].downcast_ref::<ValTag<u32>>().unwrap();
+        assert_eq!(tag.val, 7000);
+        assert_eq!(tag.meta.author.as_deref(), Some("ann"));
+        let json = serde_json::to_string(&loaded).unwrap();
+        assert!(json.contains(r#""type":"ValTag<u32>""#), "{}", json);
+    }
+}
@@ -1,31 +1,130 @@
-use tag_core::prelude::Tag;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::prelude::{Uuid, Tag, Tags, ValidationReport};
+
+pub mod value;
+pub mod registry;
+pub mod migration;
 
 pub mod val;
 pub mod map;
 
-#[typetag::serde(tag = "type")]
-pub trait SerdeTag : Tag {
+pub mod patch;
+
+#[cfg(feature = "binary")]
+pub mod binary;
+
+#[cfg(feature = "taxonomy")]
+pub mod taxonomy;
+
+/// A tag type that can be (de)serialized through `SerdeTags`.
+///
+/// The type name is what gets written to files, so it should stay stable once
+/// data is stored, and the type needs to be registered with `register_serde_tag!`
+/// to be deserializable.
+pub trait SerdeTagType : Tag + Clone + PartialEq + ::serde::Serialize + ::serde::de::DeserializeOwned + 'static {
+    fn tag_type_name() -> Cow<'static, str>;
+
+    /// Whether two tags hold the same data apart from uuid and parent.
+    fn payload_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    /// What compact encodings store besides the uuid and parent, the whole tag by default.
+    fn payload(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
+    fn from_payload(
+        _uuid: Uuid,
+        _parent: Option<Uuid>,
+        payload: &mut dyn erased_serde::Deserializer<'_>,
+    ) -> Result<Self, erased_serde::Error> {
+        erased_serde::deserialize(payload)
+    }
+}
+
+pub trait SerdeTag : Tag + erased_serde::Serialize {
+    fn tag_type_name(&self) -> Cow<'static, str>;
+    fn payload(&self) -> &dyn erased_serde::Serialize;
+
+    fn as_any(&self) -> &dyn Any;
+    fn clone_tag(&self) -> Box<dyn SerdeTag>;
+    fn tag_eq(&self, other: &dyn SerdeTag) -> bool;
+    fn payload_eq(&self, other: &dyn SerdeTag) -> bool;
+}
+
+impl<T: SerdeTagType> SerdeTag for T {
+    fn tag_type_name(&self) -> Cow<'static, str> {
+        <T as SerdeTagType>::tag_type_name()
+    }
+
+    fn payload(&self) -> &dyn erased_serde::Serialize {
+        <T as SerdeTagType>::payload(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_tag(&self) -> Box<dyn SerdeTag> {
+        Box::new(self.clone())
+    }
+
+    fn tag_eq(&self, other: &dyn SerdeTag) -> bool {
+        other.as_any().downcast_ref::<T>()
+            .map(|x| self == x)
+            .unwrap_or(false)
+    }
+
+    fn payload_eq(&self, other: &dyn SerdeTag) -> bool {
+        other.as_any().downcast_ref::<T>()
+            .map(|x| <T as SerdeTagType>::payload_eq(self, x))
+            .unwrap_or(false)
+    }
+}
+
+crate::tag::impl_dyn_tag_downcast!(
+    dyn SerdeTag,
+);
+
+impl Clone for Box<dyn SerdeTag> {
+    fn clone(&self) -> Self {
+        self.clone_tag()
+    }
 }
 
+impl PartialEq for dyn SerdeTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag_eq(other)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-pub struct SerdeTags(Vec<Box<dyn SerdeTag>>);
+pub struct SerdeTags(pub Vec<Box<dyn SerdeTag>>);
+
+impl From<SerdeTags> for Tags<dyn SerdeTag> {
+    fn from(v: SerdeTags) -> Self {
+        v.0.into_iter().collect()
+    }
+}
 
-// typetag doesn't support generic impl yet
-// https://github.com/dtolnay/typetag/issues/1
-#[macro_export]
-macro_rules! impl_serde_v_tag {
-    ($type: ident, $value_type: ident) => {
-        #[typetag::serde]
-        impl SerdeTag for $type<$value_type> {
-        }
+impl From<Tags<dyn SerdeTag>> for SerdeTags {
+    fn from(v: Tags<dyn SerdeTag>) -> Self {
+        Self(v.into_iter().collect())
     }
 }
 
-#[macro_export]
-macro_rules! impl_serde_kv_tag {
-    ($type: ident, $key_type: ident, $value_type: ident) => {
-        #[typetag::serde]
-        impl SerdeTag for $type<$key_type, $value_type> {
-        }
+impl SerdeTags {
+    pub fn iter(&self) -> impl Iterator<Item = &dyn SerdeTag> {
+        self.0.iter().map(|x| x.as_ref())
     }
-}
\ No newline at end of file
+
+    pub fn validate(&self) -> ValidationReport {
+        crate::validate::validate(self.iter())
+    }
+}
@@ -0,0 +1,159 @@
+use alloc::boxed::Box;
+
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, Tags, IndexMap, IndexSet, SerdeTag, SerdeTags, Timestamp};
+
+#[cfg(feature = "std")]
+use crate::meta::now;
+
+#[derive(Debug, Snafu)]
+pub enum PatchError {
+    #[snafu(display("Already exists: `{}`", uuid))]
+    AlreadyExists { uuid: Uuid },
+    #[snafu(display("Not found: `{}`", uuid))]
+    NotFound { uuid: Uuid },
+}
+
+pub type PatchResult<T> = core::result::Result<T, PatchError>;
+
+/// A tag present in both snapshots that changed, `tag` is the new version.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TagUpdate {
+    pub old_parent: Option<Uuid>,
+    pub reparented: bool,
+    pub value_changed: bool,
+    pub tag: Box<dyn SerdeTag>,
+}
+
+impl PartialEq for TagUpdate {
+    fn eq(&self, other: &Self) -> bool {
+        self.old_parent == other.old_parent
+            && self.reparented == other.reparented
+            && self.value_changed == other.value_changed
+            && self.tag.tag_eq(other.tag.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct TagPatch {
+    pub added: IndexMap<Uuid, Box<dyn SerdeTag>>,
+    pub removed: IndexSet<Uuid>,
+    pub updated: IndexMap<Uuid, TagUpdate>,
+}
+
+impl TagPatch {
+    /// Changes from `old` to `new`, if a uuid appears more than once the last entry is used.
+    pub fn diff<'a, 'b, O, N>(old: O, new: N) -> Self
+        where
+            O: IntoIterator<Item = &'a dyn SerdeTag>,
+            N: IntoIterator<Item = &'b dyn SerdeTag>,
+    {
+        let old: IndexMap<Uuid, &dyn SerdeTag> = old.into_iter()
+            .map(|x| (*x.uuid(), x))
+            .collect();
+        let new: IndexMap<Uuid, &dyn SerdeTag> = new.into_iter()
+            .map(|x| (*x.uuid(), x))
+            .collect();
+        let mut patch = Self::default();
+        for (uuid, tag) in new.iter() {
+            let Some(old_tag) = old.get(uuid) else {
+                patch.added.insert(*uuid, tag.clone_tag());
+                continue;
+            };
+            if old_tag.tag_eq(*tag) {
+                continue;
+            }
+            patch.updated.insert(*uuid, TagUpdate {
+                old_parent: old_tag.parent().copied(),
+                reparented: old_tag.parent() != tag.parent(),
+                value_changed: !old_tag.payload_eq(*tag),
+                tag: tag.clone_tag(),
+            });
+        }
+        for uuid in old.keys() {
+            if !new.contains_key(uuid) {
+                patch.removed.insert(*uuid);
+            }
+        }
+        patch
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+
+    pub fn reparented(&self) -> impl Iterator<Item = &TagUpdate> {
+        self.updated.values().filter(|x| x.reparented)
+    }
+
+    pub fn value_changed(&self) -> impl Iterator<Item = &TagUpdate> {
+        self.updated.values().filter(|x| x.value_changed)
+    }
+
+    /// Apply the patch as an edit, see `apply_at`.
+    #[cfg(feature = "std")]
+    pub fn apply(self, tags: &mut Tags<dyn SerdeTag>) -> PatchResult<()> {
+        self.apply_at(tags, now())
+    }
+
+    /// Apply the patch, nothing is changed if any entry conflicts with `tags`.
+    ///
+    /// Added and updated tags are stamped like `Tags::upsert_at`.
+    pub fn apply_at(self, tags: &mut Tags<dyn SerdeTag>, at: Timestamp) -> PatchResult<()> {
+        for uuid in self.added.keys() {
+            ensure!(!tags.contains(uuid), AlreadyExistsSnafu { uuid: *uuid });
+        }
+        for uuid in self.removed.iter().chain(self.updated.keys()) {
+            ensure!(tags.contains(uuid), NotFoundSnafu { uuid: *uuid });
+        }
+        for uuid in self.removed.iter() {
+            tags.remove(uuid);
+        }
+        for tag in self.updated.into_values().map(|x| x.tag).chain(self.added.into_values()) {
+            tags.upsert_at(tag, at);
+        }
+        Ok(())
+    }
+}
+
+impl SerdeTags {
+    pub fn diff(&self, new: &SerdeTags) -> TagPatch {
+        TagPatch::diff(self.iter(), new.iter())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn apply(self, patch: TagPatch) -> PatchResult<SerdeTags> {
+        self.apply_at(patch, now())
+    }
+
+    pub fn apply_at(self, patch: TagPatch, at: Timestamp) -> PatchResult<SerdeTags> {
+        let mut tags: Tags<dyn SerdeTag> = self.into();
+        patch.apply_at(&mut tags, at)?;
+        Ok(tags.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+
+    use super::*;
+    use crate::prelude::{ValTag, TagMeta};
+
+    fn tag(uuid: u128, val: &str, meta: TagMeta) -> Box<dyn SerdeTag> {
+        Box::new(ValTag { uuid: Uuid::from_u128(uuid), parent: None, val: val.to_string(), meta })
+    }
+
+    #[test]
+    fn apply_stamps_tags() {
+        let old = SerdeTags(vec![tag(1, "a", TagMeta { created: Some(1), ..Default::default() })]);
+        let new = SerdeTags(vec![tag(1, "b", TagMeta::default()), tag(2, "c", TagMeta::default())]);
+        let applied = old.clone().apply_at(old.diff(&new), 10).unwrap();
+        let meta = |uuid| applied.iter().find(|x| x.uuid() == &Uuid::from_u128(uuid)).and_then(|x| x.meta()).cloned();
+        assert_eq!(meta(1), Some(TagMeta { created: Some(1), modified: Some(10), ..Default::default() }));
+        assert_eq!(meta(2), Some(TagMeta { created: Some(10), ..Default::default() }));
+        assert_eq!(applied.iter().find(|x| x.uuid() == &Uuid::from_u128(1)).and_then(|x| x.val::<String>()), Some(&"b".to_string()));
+    }
+}
@@ -0,0 +1,321 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+use once_cell::race::OnceBox;
+
+use ::serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use ::serde::ser::SerializeStruct;
+
+use crate::prelude::{Uuid, IndexMap, Tag, SerdeTag, SerdeTagType};
+use crate::serde::migration::Migration;
+
+#[doc(hidden)]
+pub use inventory;
+
+// for `#[derive(Tag)]` expansions, which can't name `std` or `alloc`
+#[doc(hidden)]
+pub use alloc::borrow::Cow;
+
+pub type DeserializeFn = fn(&mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error>;
+pub type FromPayloadFn = fn(Uuid, Option<Uuid>, &mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error>;
+pub type ToSerdeFn = fn(&dyn Tag) -> Option<Box<dyn SerdeTag>>;
+pub type IntoSharedFn = fn(Box<dyn SerdeTag>) -> Option<Arc<dyn Tag + Send + Sync>>;
+
+pub struct Registration {
+    pub tag_type_name: fn() -> Cow<'static, str>,
+    pub deserialize: DeserializeFn,
+    pub from_payload: FromPayloadFn,
+    pub type_id: fn() -> TypeId,
+    pub to_serde: ToSerdeFn,
+    pub into_shared: IntoSharedFn,
+}
+
+impl Registration {
+    pub const fn of<T: SerdeTagType + Send + Sync>() -> Self {
+        Self {
+            tag_type_name: <T as SerdeTagType>::tag_type_name,
+            deserialize: deserialize_tag::<T>,
+            from_payload: tag_from_payload::<T>,
+            type_id: TypeId::of::<T>,
+            to_serde: tag_to_serde::<T>,
+            into_shared: tag_into_shared::<T>,
+        }
+    }
+}
+
+fn deserialize_tag<T: SerdeTagType>(deserializer: &mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error> {
+    Ok(Box::new(erased_serde::deserialize::<T>(deserializer)?))
+}
+
+fn tag_from_payload<T: SerdeTagType>(uuid: Uuid, parent: Option<Uuid>, payload: &mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeTag>, erased_serde::Error> {
+    Ok(Box::new(T::from_payload(uuid, parent, payload)?))
+}
+
+pub(crate) fn tag_to_serde<T: SerdeTagType>(tag: &dyn Tag) -> Option<Box<dyn SerdeTag>> {
+    tag.downcast_ref::<T>().map(|x| Box::new(x.clone()) as Box<dyn SerdeTag>)
+}
+
+pub(crate) fn tag_into_shared<T: SerdeTagType + Send + Sync>(tag: Box<dyn SerdeTag>) -> Option<Arc<dyn Tag + Send + Sync>> {
+    let tag: Box<dyn Any> = tag;
+    tag.downcast::<T>().ok().map(|x| Arc::new(*x) as Arc<dyn Tag + Send + Sync>)
+}
+
+inventory::collect!(Registration);
+
+/// Make tag types deserializable through `SerdeTags`.
+///
+/// Each concrete type should be registered once across all linked crates,
+/// two registrations with the same name make that name unusable. Registered
+/// types need to be `Send + Sync` so they can be shared as `Arc`s.
+#[macro_export]
+macro_rules! register_serde_tag {
+    ($($type: ty),* $(,)?) => {
+        $(
+            $crate::serde::registry::inventory::submit! {
+                $crate::serde::registry::Registration::of::<$type>()
+            }
+        )*
+    }
+}
+
+// None marks a name registered more than once
+type Registry = IndexMap<Cow<'static, str>, Option<&'static Registration>>;
+
+// racing first calls may each build it, all but one are dropped
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceBox<Registry> = OnceBox::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = Registry::default();
+        for registration in inventory::iter::<Registration> {
+            registry.entry((registration.tag_type_name)())
+                .and_modify(|x| *x = None)
+                .or_insert(Some(registration));
+        }
+        let mut migrations = Registry::default();
+        for migration in inventory::iter::<Migration> {
+            migrations.entry(migration.from_type_name())
+                .and_modify(|x| *x = None)
+                .or_insert(Some(&migration.0));
+        }
+        registry.extend(migrations);
+        Box::new(registry)
+    })
+}
+
+type TypeRegistry = IndexMap<TypeId, Option<&'static Registration>>;
+
+// migrations are left out, they are registered for the types they migrate from
+fn type_registry() -> &'static TypeRegistry {
+    static REGISTRY: OnceBox<TypeRegistry> = OnceBox::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = TypeRegistry::default();
+        for registration in inventory::iter::<Registration> {
+            registry.entry((registration.type_id)())
+                .and_modify(|x| *x = None)
+                .or_insert(Some(registration));
+        }
+        Box::new(registry)
+    })
+}
+
+pub fn is_registered(tag_type_name: &str) -> bool {
+    matches!(registry().get(tag_type_name), Some(Some(_)))
+}
+
+pub fn registered_names() -> impl Iterator<Item = &'static str> {
+    registry().keys().map(|x| x.as_ref())
+}
+
+// aliases older versions wrote in type names, `MapTag<K, V>` is now `ValTag<Map<K, V>>`
+const LEGACY_ALIASES: &[(&str, &str)] = &[
+    ("VecTag<", "Vec<"),
+    ("SetTag<", "Set<"),
+    ("IndexSetTag<", "IndexSet<"),
+    ("MapTag<", "Map<"),
+    ("IndexMapTag<", "IndexMap<"),
+];
+
+fn legacy_name(tag_type_name: &str) -> Option<String> {
+    LEGACY_ALIASES.iter().find_map(|(alias, value_type)| {
+        let params = tag_type_name.strip_prefix(alias)?;
+        Some(format!("ValTag<{}{}>", value_type, params))
+    })
+}
+
+/// Also resolves names written by older versions, see `LEGACY_ALIASES`.
+pub fn get_registration(tag_type_name: &str) -> Result<&'static Registration, String> {
+    let legacy = legacy_name(tag_type_name);
+    let found = registry().get(tag_type_name)
+        .or_else(|| legacy.as_deref().and_then(|x| registry().get(x)));
+    match found {
+        Some(Some(registration)) => Ok(registration),
+        Some(None) => Err(format!("ambiguous tag type: `{}`", tag_type_name)),
+        None => Err(format!("unknown tag type: `{}`", tag_type_name)),
+    }
+}
+
+/// Registration of the concrete type of a tag.
+pub fn get_registration_of(tag: &dyn Tag) -> Result<&'static Registration, String> {
+    let type_id = (tag as &dyn Any).type_id();
+    match type_registry().get(&type_id) {
+        Some(Some(registration)) => Ok(registration),
+        Some(None) => Err(format!("ambiguous tag type of: `{}`", tag.uuid())),
+        None => Err(format!("unregistered tag type of: `{}`", tag.uuid())),
+    }
+}
+
+/// Copy of a tag of a registered type as a `SerdeTag`.
+pub fn to_serde_tag(tag: &dyn Tag) -> Result<Box<dyn SerdeTag>, String> {
+    let registration = get_registration_of(tag)?;
+    (registration.to_serde)(tag)
+        .ok_or_else(|| format!("mismatched registration of: `{}`", tag.uuid()))
+}
+
+/// A `SerdeTag` as a shared tag, e.g. to be a proto of model tags.
+pub fn into_shared_tag(tag: Box<dyn SerdeTag>) -> Result<Arc<dyn Tag + Send + Sync>, String> {
+    let uuid = *tag.uuid();
+    let registration = get_registration_of(tag.as_ref())?;
+    (registration.into_shared)(tag)
+        .ok_or_else(|| format!("mismatched registration of: `{}`", uuid))
+}
+
+const FIELDS: &[&str] = &["type", "tag"];
+
+impl Serialize for dyn SerdeTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SerdeTag", FIELDS.len())?;
+        state.serialize_field(FIELDS[0], &self.tag_type_name())?;
+        state.serialize_field(FIELDS[1], &ErasedTag(self))?;
+        state.end()
+    }
+}
+
+struct ErasedTag<'a>(&'a dyn SerdeTag);
+
+impl<'a> Serialize for ErasedTag<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        erased_serde::serialize(self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn SerdeTag> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("SerdeTag", FIELDS, TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> de::Visitor<'de> for TagVisitor {
+    type Value = Box<dyn SerdeTag>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tag with its type name")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let name: String = seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let registration = get_registration(&name).map_err(de::Error::custom)?;
+        seq.next_element_seed(TagSeed(registration))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+
+    // the type name has to come first, which is how tags are serialized, the
+    // fields may follow inline as older versions wrote them
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        match map.next_key::<String>()? {
+            Some(key) if key == FIELDS[0] => {},
+            Some(key) => return Err(de::Error::custom(format!("expected field `type` first, got `{}`", key))),
+            None => return Err(de::Error::missing_field(FIELDS[0])),
+        }
+        let name: String = map.next_value()?;
+        let registration = get_registration(&name).map_err(de::Error::custom)?;
+        match map.next_key::<String>()? {
+            Some(key) if key == FIELDS[1] => map.next_value_seed(TagSeed(registration)),
+            Some(key) => {
+                let fields = InlineFields { first: Some(key), map };
+                de::DeserializeSeed::deserialize(TagSeed(registration), de::value::MapAccessDeserializer::new(fields))
+            },
+            None => Err(de::Error::missing_field(FIELDS[1])),
+        }
+    }
+}
+
+// the rest of a map whose first key was already read
+struct InlineFields<A> {
+    first: Option<String>,
+    map: A,
+}
+
+impl<'de, A: de::MapAccess<'de>> de::MapAccess<'de> for InlineFields<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error> {
+        match self.first.take() {
+            Some(key) => seed.deserialize(de::value::StringDeserializer::new(key)).map(Some),
+            None => self.map.next_key_seed(seed),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+struct TagSeed(&'static Registration);
+
+impl<'de> de::DeserializeSeed<'de> for TagSeed {
+    type Value = Box<dyn SerdeTag>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.0.deserialize)(&mut deserializer).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+
+    use crate::prelude::{Uuid, Tag, CoreTag, TagMeta, SerdeTags};
+    use super::is_registered;
+
+    #[derive(Debug, Clone, PartialEq, crate::prelude::Tag, serde::Serialize, serde::Deserialize)]
+    #[tag(crate = "crate", serde = "Genre")]
+    struct Genre {
+        #[tag(uuid)]
+        id: Uuid,
+        parent: Option<Uuid>,
+        name: String,
+        #[tag(meta)]
+        info: TagMeta,
+    }
+
+    #[test]
+    fn derived_tags_round_trip() {
+        let genre = Genre {
+            id: Uuid::from_u128(1),
+            parent: Some(Uuid::from_u128(2)),
+            name: "jazz".to_string(),
+            info: TagMeta::default().with_author("ann"),
+        };
+        assert_eq!(genre.uuid(), &Uuid::from_u128(1));
+        assert_eq!(genre.parent(), Some(&Uuid::from_u128(2)));
+        assert_eq!(genre.meta().and_then(|x| x.author.as_deref()), Some("ann"));
+        assert!(is_registered("Genre"));
+
+        let tags = SerdeTags(vec![Box::new(genre.clone())]);
+        let json = serde_json::to_string(&tags).unwrap();
+        assert!(json.contains(r#""type":"Genre""#), "{}", json);
+        let loaded: SerdeTags = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, tags);
+        assert_eq!(loaded.0[0].downcast_ref::<Genre>(), Some(&genre));
+    }
+}
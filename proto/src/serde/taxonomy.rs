@@ -0,0 +1,445 @@
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, IndexMap, IndexSet, Tags, TagMeta, TagNames, PathError, SerdeTag, SerdeTags, path_uuid};
+use crate::names::{SEPARATOR, is_valid_name};
+use crate::serde::registry::get_registration;
+
+/// Keys with a meaning inside a node, they can't be used as tag names.
+pub const RESERVED_NAMES: &[&str] = &["uuid", "parent", "type", "value", "meta", "namespace"];
+
+#[derive(Debug, Snafu)]
+pub enum TaxonomyError {
+    #[snafu(display("Parse failed: {}", info))]
+    ParseFailed { info: String },
+    #[snafu(display("Write failed: {}", info))]
+    WriteFailed { info: String },
+    #[snafu(display("Invalid name: `{}`", name))]
+    InvalidName { name: String },
+    #[snafu(display("Missing uuid: `{}`", path))]
+    MissingUuid { path: String },
+    #[snafu(display("Missing type: `{}`", path))]
+    MissingType { path: String },
+    #[snafu(display("Unknown type: `{}` -> {}", path, info))]
+    UnknownType { path: String, info: String },
+    #[snafu(display("Value failed: `{}` -> {}", path, info))]
+    ValueFailed { path: String, info: String },
+    #[snafu(display("Tag mismatch: `{}`", path))]
+    TagMismatch { path: String },
+    #[snafu(display("Duplicate uuid: `{}`", uuid))]
+    DuplicateUuid { uuid: Uuid },
+    #[snafu(display("Unreachable tag: `{}`", uuid))]
+    Unreachable { uuid: Uuid },
+    #[snafu(display("No TOML form: `{}`", path))]
+    NoTomlForm { path: String },
+    #[snafu(display("Naming failed: {}", source))]
+    NamingFailed { source: PathError },
+}
+
+pub type TaxonomyResult<T> = std::result::Result<T, TaxonomyError>;
+
+/// Tag hierarchy in a form meant to be written by hand, as TOML or YAML.
+///
+/// Every key that isn't a field is a child tag named by the key, e.g.
+///
+/// ```toml
+/// namespace = "6e412385-afff-40bc-8a2f-a031d174201c"
+///
+/// [genre.drama]
+/// meta = { author = "ann" }
+///
+/// [year]
+/// type = "ValTag<u16>"
+/// value = 1994
+/// ```
+///
+/// A node without `type` is a `ValTag` of the scalar or list type its value looks
+/// like, a node without `value` is a `ValTag<String>` holding its name. Uuids
+/// can be left out, they are derived from the path under `namespace` or filled
+/// in by `fill_uuids`. `parent` is only used on top level nodes, for tags under
+/// a tag that isn't in the file.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Taxonomy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<Uuid>,
+    #[serde(flatten)]
+    pub tags: IndexMap<String, TaxonomyNode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct TaxonomyNode {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Uuid>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub tag_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "TagMeta::is_empty")]
+    pub meta: TagMeta,
+    #[serde(flatten)]
+    pub children: IndexMap<String, TaxonomyNode>,
+}
+
+fn is_valid_node_name(name: &str) -> bool {
+    is_valid_name(name) && !RESERVED_NAMES.contains(&name)
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}{}{}", path, SEPARATOR, name)
+    }
+}
+
+/// Tag type a value gets when the node doesn't name one.
+pub fn infer_type(value: &serde_json::Value) -> Option<String> {
+    fn scalar(value: &serde_json::Value) -> Option<&'static str> {
+        match value {
+            serde_json::Value::Bool(_) => Some("bool"),
+            serde_json::Value::Number(v) if v.is_i64() => Some("i64"),
+            serde_json::Value::Number(v) if v.is_u64() => Some("u64"),
+            serde_json::Value::Number(_) => Some("f64"),
+            serde_json::Value::String(_) => Some("String"),
+            _ => None,
+        }
+    }
+    match value {
+        serde_json::Value::Array(items) => {
+            let kinds: IndexSet<&str> = items.iter().map(scalar).collect::<Option<_>>()?;
+            match kinds.len() {
+                1 => Some(format!("ValTag<Vec<{}>>", kinds[0])),
+                _ => None,
+            }
+        },
+        value => scalar(value).map(|x| format!("ValTag<{}>", x)),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn find_no_toml_form(path: &str, nodes: &IndexMap<String, TaxonomyNode>) -> Option<String> {
+    fn has_toml_form(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Null => false,
+            serde_json::Value::Number(v) => v.is_i64() || !v.is_u64(),
+            serde_json::Value::Array(items) => items.iter().all(has_toml_form),
+            serde_json::Value::Object(fields) => fields.values().all(has_toml_form),
+            _ => true,
+        }
+    }
+    nodes.iter().find_map(|(name, node)| {
+        let path = join_path(path, name);
+        match node.value.as_ref().map(has_toml_form) {
+            Some(false) => Some(path),
+            _ => find_no_toml_form(&path, &node.children),
+        }
+    })
+}
+
+impl Taxonomy {
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> TaxonomyResult<Self> {
+        toml::from_str(text).map_err(|x| TaxonomyError::ParseFailed { info: x.to_string() })
+    }
+
+    /// Fails with `NoTomlForm` for values TOML can't hold, nulls and integers
+    /// above `i64::MAX`, YAML keeps those.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> TaxonomyResult<String> {
+        if let Some(path) = find_no_toml_form("", &self.tags) {
+            return NoTomlFormSnafu { path }.fail();
+        }
+        toml::to_string_pretty(self).map_err(|x| TaxonomyError::WriteFailed { info: x.to_string() })
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(text: &str) -> TaxonomyResult<Self> {
+        serde_yaml::from_str(text).map_err(|x| TaxonomyError::ParseFailed { info: x.to_string() })
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self) -> TaxonomyResult<String> {
+        serde_yaml::to_string(self).map_err(|x| TaxonomyError::WriteFailed { info: x.to_string() })
+    }
+
+    /// Give every node without a uuid one, derived from its path under `namespace`
+    /// or random without one. Returns how many were assigned, random ones need
+    /// to be written back to keep them.
+    pub fn fill_uuids(&mut self) -> usize {
+        fn fill(namespace: Option<&Uuid>, path: &str, nodes: &mut IndexMap<String, TaxonomyNode>) -> usize {
+            let mut count = 0;
+            for (name, node) in nodes.iter_mut() {
+                let path = join_path(path, name);
+                if node.uuid.is_none() {
+                    node.uuid = Some(namespace.map(|x| path_uuid(x, &path)).unwrap_or_else(Uuid::new_v4));
+                    count += 1;
+                }
+                count += fill(namespace, &path, &mut node.children);
+            }
+            count
+        }
+        fill(self.namespace.as_ref(), "", &mut self.tags)
+    }
+
+    /// Tags in depth-first order with their names, nodes need a uuid unless `namespace` is set.
+    pub fn to_tags(&self) -> TaxonomyResult<(SerdeTags, TagNames)> {
+        let mut tags: Tags<dyn SerdeTag> = Tags::new();
+        let mut names = TagNames::new();
+        for (name, node) in self.tags.iter() {
+            self.read_node(&mut tags, &mut names, node.parent, "", name, node)?;
+        }
+        Ok((tags.into(), names))
+    }
+
+    fn read_node(
+        &self,
+        tags: &mut Tags<dyn SerdeTag>,
+        names: &mut TagNames,
+        parent: Option<Uuid>,
+        path: &str,
+        name: &str,
+        node: &TaxonomyNode,
+    ) -> TaxonomyResult<()> {
+        ensure!(is_valid_node_name(name), InvalidNameSnafu { name });
+        let path = join_path(path, name);
+        let uuid = match (node.uuid, self.namespace.as_ref()) {
+            (Some(uuid), _) => uuid,
+            (None, Some(namespace)) => path_uuid(namespace, &path),
+            (None, None) => return MissingUuidSnafu { path }.fail(),
+        };
+        ensure!(!tags.contains(&uuid), DuplicateUuidSnafu { uuid });
+        let value = node.value.clone()
+            .unwrap_or_else(|| serde_json::Value::String(name.to_owned()));
+        let tag_type = match &node.tag_type {
+            Some(tag_type) => tag_type.clone(),
+            None => infer_type(&value).context(MissingTypeSnafu { path: &path })?,
+        };
+        let registration = get_registration(&tag_type)
+            .map_err(|info| TaxonomyError::UnknownType { path: path.clone(), info })?;
+        let mut erased = <dyn erased_serde::Deserializer>::erase(value);
+        let mut tag = (registration.from_payload)(uuid, parent, &mut erased)
+            .map_err(|x| TaxonomyError::ValueFailed { path: path.clone(), info: x.to_string() })?;
+        ensure!(tag.uuid() == &uuid && tag.parent() == parent.as_ref(), TagMismatchSnafu { path: &path });
+        if let Some(meta) = tag.meta_mut() {
+            meta.clone_from(&node.meta);
+        }
+        tags.insert(tag);
+        names.set_name(tags, &uuid, name).context(NamingFailedSnafu)?;
+        for (child_name, child) in node.children.iter() {
+            self.read_node(tags, names, Some(uuid), &path, child_name, child)?;
+        }
+        Ok(())
+    }
+
+    /// Nest tags under their parents, named by `names` where possible.
+    ///
+    /// Tags without a usable name are named by their `ValTag<String>` value or
+    /// else their uuid. Uuids are left out where `namespace` derives the same one.
+    pub fn from_tags<'a, I>(tags: I, names: &TagNames, namespace: Option<Uuid>) -> TaxonomyResult<Self>
+        where I: IntoIterator<Item = &'a dyn SerdeTag>
+    {
+        let mut by_uuid: IndexMap<Uuid, &dyn SerdeTag> = IndexMap::new();
+        for tag in tags {
+            let uuid = *tag.uuid();
+            ensure!(by_uuid.insert(uuid, tag).is_none(), DuplicateUuidSnafu { uuid });
+        }
+        let mut children: IndexMap<Option<Uuid>, Vec<Uuid>> = IndexMap::new();
+        for (uuid, tag) in by_uuid.iter() {
+            let parent = tag.parent().filter(|x| by_uuid.contains_key(*x)).copied();
+            children.entry(parent).or_default().push(*uuid);
+        }
+        let writer = Writer { by_uuid: &by_uuid, children: &children, names, namespace };
+        let mut visited = IndexSet::new();
+        let tags = writer.write_nodes(None, "", &mut visited)?;
+        if let Some(uuid) = by_uuid.keys().find(|x| !visited.contains(*x)) {
+            return UnreachableSnafu { uuid: *uuid }.fail();
+        }
+        Ok(Self { namespace, tags })
+    }
+}
+
+struct Writer<'a, 'b> {
+    by_uuid: &'b IndexMap<Uuid, &'a dyn SerdeTag>,
+    children: &'b IndexMap<Option<Uuid>, Vec<Uuid>>,
+    names: &'b TagNames,
+    namespace: Option<Uuid>,
+}
+
+impl<'a, 'b> Writer<'a, 'b> {
+    fn write_nodes(&self, parent: Option<Uuid>, path: &str, visited: &mut IndexSet<Uuid>) -> TaxonomyResult<IndexMap<String, TaxonomyNode>> {
+        let mut nodes = IndexMap::new();
+        for uuid in self.children.get(&parent).into_iter().flatten() {
+            if !visited.insert(*uuid) {
+                continue;
+            }
+            let tag = self.by_uuid[uuid];
+            let value = serde_json::to_value(tag.payload())
+                .map_err(|x| TaxonomyError::WriteFailed { info: x.to_string() })?;
+            let tag_type = tag.tag_type_name();
+            let name = [self.names.name_of(uuid), value.as_str().filter(|_| tag_type == "ValTag<String>")]
+                .into_iter()
+                .flatten()
+                .find(|x| is_valid_node_name(x) && !nodes.contains_key(*x))
+                .map(|x| x.to_owned())
+                .unwrap_or_else(|| uuid.to_string());
+            let path = join_path(path, &name);
+            let derived = self.namespace.map(|x| path_uuid(&x, &path));
+            let node = TaxonomyNode {
+                uuid: (derived != Some(*uuid)).then_some(*uuid),
+                parent: parent.is_none().then(|| tag.parent().copied()).flatten(),
+                tag_type: (infer_type(&value).as_deref() != Some(&*tag_type)).then(|| tag_type.to_string()),
+                value: (tag_type != "ValTag<String>" || value.as_str() != Some(&name)).then_some(value),
+                meta: tag.meta().cloned().unwrap_or_default(),
+                children: self.write_nodes(Some(*uuid), &path, visited)?,
+            };
+            nodes.insert(name, node);
+        }
+        Ok(nodes)
+    }
+}
+
+impl SerdeTags {
+    pub fn to_taxonomy(&self, names: &TagNames, namespace: Option<Uuid>) -> TaxonomyResult<Taxonomy> {
+        Taxonomy::from_tags(self.iter(), names, namespace)
+    }
+}
+
+#[cfg(all(test, any(feature = "toml", feature = "yaml")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "toml")]
+    const TOML: &str = r#"
+namespace = "6e412385-afff-40bc-8a2f-a031d174201c"
+
+[genre]
+meta = { author = "ann", created = 10 }
+
+[genre.drama]
+uuid = "00000000-0000-0000-0000-000000000007"
+
+[genre.drama.noir]
+value = "Film Noir"
+
+[year]
+type = "ValTag<u16>"
+value = 1994
+
+[score]
+value = 7.5
+
+[rank]
+value = -3
+
+[flag]
+value = false
+
+[langs]
+value = ["en", "fr"]
+
+[counts]
+type = "ValTag<IndexMap<String, u32>>"
+value = { b = 2, a = 1 }
+
+[ext]
+parent = "00000000-0000-0000-0000-000000000009"
+meta = { source = "import" }
+"#;
+
+    #[cfg(feature = "yaml")]
+    const YAML: &str = r#"
+namespace: 6e412385-afff-40bc-8a2f-a031d174201c
+genre:
+  meta:
+    author: ann
+    created: 10
+  drama:
+    uuid: 00000000-0000-0000-0000-000000000007
+    noir:
+      value: Film Noir
+year:
+  type: ValTag<u16>
+  value: 1994
+score:
+  value: 7.5
+rank:
+  value: -3
+counts:
+  type: ValTag<IndexMap<String, u32>>
+  value:
+    b: 2
+    a: 1
+optional:
+  type: ValTag<Vec<Option<String>>>
+  value: [a, null]
+big:
+  value: 18446744073709551615
+"#;
+
+    // taxonomy -> tags -> taxonomy, the tags of both have to be the same
+    fn through_tags(taxonomy: &Taxonomy) -> Taxonomy {
+        let (tags, names) = taxonomy.to_tags().unwrap();
+        let back = tags.to_taxonomy(&names, taxonomy.namespace).unwrap();
+        let (again, _) = back.to_tags().unwrap();
+        assert_eq!(again, tags);
+        back
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trip() {
+        let taxonomy = Taxonomy::from_toml(TOML).unwrap();
+        let (tags, names) = taxonomy.to_tags().unwrap();
+        let types: Vec<_> = tags.iter().map(|x| x.tag_type_name()).collect();
+        assert_eq!(types, [
+            "ValTag<String>", "ValTag<String>", "ValTag<String>", "ValTag<u16>", "ValTag<f64>",
+            "ValTag<i64>", "ValTag<bool>", "ValTag<Vec<String>>", "ValTag<IndexMap<String, u32>>", "ValTag<String>",
+        ]);
+        assert_eq!(tags.0[1].uuid(), &Uuid::from_u128(7));
+        assert_eq!(tags.0[2].parent(), Some(&Uuid::from_u128(7)));
+        assert_eq!(names.name_of(tags.0[2].uuid()), Some("noir"));
+        assert_eq!(tags.0[0].meta().map(|x| (x.author.as_deref(), x.created)), Some((Some("ann"), Some(10))));
+        assert_eq!(tags.0[9].parent(), Some(&Uuid::from_u128(9)));
+
+        let back = through_tags(&taxonomy);
+        assert_eq!(back, taxonomy);
+        let text = back.to_toml().unwrap();
+        assert_eq!(Taxonomy::from_toml(&text).unwrap(), taxonomy);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trip() {
+        let taxonomy = Taxonomy::from_yaml(YAML).unwrap();
+        let (tags, _) = taxonomy.to_tags().unwrap();
+        let optional = tags.iter().find(|x| x.tag_type_name() == "ValTag<Vec<Option<String>>>").unwrap();
+        assert_eq!(optional.val::<Vec<Option<String>>>(), Some(&vec![Some("a".to_string()), None]));
+        assert_eq!(tags.0.last().and_then(|x| x.val::<u64>()), Some(&u64::MAX));
+
+        let back = through_tags(&taxonomy);
+        assert_eq!(back, taxonomy);
+        let text = back.to_yaml().unwrap();
+        assert_eq!(Taxonomy::from_yaml(&text).unwrap(), taxonomy);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_rejects_values_without_form() {
+        let mut taxonomy = Taxonomy { namespace: Some(Uuid::from_u128(1)), ..Default::default() };
+        let mut parent = TaxonomyNode::default();
+        parent.children.insert("optional".into(), TaxonomyNode {
+            tag_type: Some("ValTag<Vec<Option<String>>>".into()),
+            value: Some(serde_json::json!(["a", null])),
+            ..Default::default()
+        });
+        taxonomy.tags.insert("parent".into(), parent);
+        assert!(matches!(taxonomy.to_toml(), Err(TaxonomyError::NoTomlForm { path }) if path == "parent/optional"));
+
+        let mut taxonomy = Taxonomy::default();
+        taxonomy.tags.insert("big".into(), TaxonomyNode { value: Some(serde_json::json!(u64::MAX)), ..Default::default() });
+        assert!(matches!(taxonomy.to_toml(), Err(TaxonomyError::NoTomlForm { path }) if path == "big"));
+        taxonomy.tags[0].value = Some(serde_json::json!(i64::MAX as u64));
+        assert!(taxonomy.to_toml().is_ok());
+    }
+}
@@ -1,25 +1,42 @@
-use crate::prelude::{SerdeTag, ValTag, VecTag};
-
-use crate::impl_serde_v_tag;
-
-macro_rules! impl_serde_value_tags {
-    ($type: ident) => {
-        impl_serde_v_tag!($type, bool);
-        impl_serde_v_tag!($type, u8);
-        impl_serde_v_tag!($type, i8);
-        impl_serde_v_tag!($type, u16);
-        impl_serde_v_tag!($type, i16);
-        impl_serde_v_tag!($type, u32);
-        impl_serde_v_tag!($type, i32);
-        impl_serde_v_tag!($type, u64);
-        impl_serde_v_tag!($type, i64);
-        impl_serde_v_tag!($type, u128);
-        impl_serde_v_tag!($type, i128);
-        impl_serde_v_tag!($type, f32);
-        impl_serde_v_tag!($type, f64);
-        impl_serde_v_tag!($type, String);
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::prelude::{Uuid, ValTag, VecTag, DynTag, Bytes};
+
+use crate::register_serde_tag;
+
+macro_rules! register_value_tags {
+    ($($value_type: ty),* $(,)?) => {
+        register_serde_tag!($(ValTag<$value_type>, VecTag<$value_type>),*);
     }
 }
 
-impl_serde_value_tags!(ValTag);
-impl_serde_value_tags!(VecTag);
+register_value_tags!(
+    bool,
+    u8, i8, u16, i16, u32, i32, u64, i64, u128, i128,
+    f32, f64,
+    String,
+    Uuid,
+    Bytes,
+);
+
+#[cfg(feature = "std")]
+register_value_tags!(
+    crate::geo::Point,
+    crate::geo::Polygon,
+    crate::geo::Geometry,
+);
+
+#[cfg(feature = "temporal")]
+register_value_tags!(
+    chrono::NaiveDate,
+    chrono::DateTime<chrono::Utc>,
+    crate::temporal::IsoDuration,
+);
+
+register_serde_tag!(
+    VecTag<Vec<String>>,
+    VecTag<Option<String>>,
+);
+
+register_serde_tag!(DynTag);
@@ -0,0 +1,173 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::hash::Hash;
+
+#[cfg(feature = "std")]
+use std::collections::{HashSet, HashMap};
+
+use crate::prelude::{Uuid, IndexSet, IndexMap, ValTag, TagMeta, Value, Bytes, SerdeTagType};
+
+/// A value type that can be carried by a `ValTag` in `SerdeTags`.
+///
+/// Containers compose the names of their elements, e.g. `Map<String, Vec<u32>>`,
+/// so a downstream type only needs to name itself.
+pub trait SerdeValue : Debug + Clone + PartialEq + ::serde::Serialize + ::serde::de::DeserializeOwned + 'static {
+    fn value_type_name() -> Cow<'static, str>;
+}
+
+impl<V: SerdeValue> SerdeTagType for ValTag<V> {
+    fn tag_type_name() -> Cow<'static, str> {
+        format!("ValTag<{}>", V::value_type_name()).into()
+    }
+
+    fn payload_eq(&self, other: &Self) -> bool {
+        self.val == other.val
+    }
+
+    fn payload(&self) -> &dyn erased_serde::Serialize {
+        &self.val
+    }
+
+    fn from_payload(
+        uuid: Uuid,
+        parent: Option<Uuid>,
+        payload: &mut dyn erased_serde::Deserializer<'_>,
+    ) -> Result<Self, erased_serde::Error> {
+        Ok(ValTag {
+            uuid,
+            parent,
+            val: erased_serde::deserialize(payload)?,
+            meta: TagMeta::default(),
+        })
+    }
+}
+
+macro_rules! impl_serde_value {
+    ($($value_type: ty => $name: literal),* $(,)?) => {
+        $(
+            impl SerdeValue for $value_type {
+                fn value_type_name() -> Cow<'static, str> {
+                    Cow::Borrowed($name)
+                }
+            }
+        )*
+    }
+}
+
+impl_serde_value!(
+    bool => "bool",
+    u8 => "u8",
+    i8 => "i8",
+    u16 => "u16",
+    i16 => "i16",
+    u32 => "u32",
+    i32 => "i32",
+    u64 => "u64",
+    i64 => "i64",
+    u128 => "u128",
+    i128 => "i128",
+    f32 => "f32",
+    f64 => "f64",
+    String => "String",
+    Uuid => "Uuid",
+    Value => "Value",
+    Bytes => "Bytes",
+);
+
+#[cfg(feature = "std")]
+impl_serde_value!(
+    crate::geo::Point => "Point",
+    crate::geo::Polygon => "Polygon",
+    crate::geo::Geometry => "Geometry",
+);
+
+#[cfg(feature = "temporal")]
+impl_serde_value!(
+    chrono::NaiveDate => "Date",
+    chrono::DateTime<chrono::Utc> => "DateTime",
+    crate::temporal::IsoDuration => "Duration",
+);
+
+impl<V: SerdeValue> SerdeValue for Option<V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("Option<{}>", V::value_type_name()).into()
+    }
+}
+
+impl<V: SerdeValue> SerdeValue for Vec<V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("Vec<{}>", V::value_type_name()).into()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: SerdeValue + Eq + Hash> SerdeValue for HashSet<V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("Set<{}>", V::value_type_name()).into()
+    }
+}
+
+impl<V: SerdeValue + Eq + Hash> SerdeValue for IndexSet<V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("IndexSet<{}>", V::value_type_name()).into()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: SerdeValue + Eq + Hash, V: SerdeValue> SerdeValue for HashMap<K, V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("Map<{}, {}>", K::value_type_name(), V::value_type_name()).into()
+    }
+}
+
+impl<K: SerdeValue + Eq + Hash, V: SerdeValue> SerdeValue for IndexMap<K, V> {
+    fn value_type_name() -> Cow<'static, str> {
+        format!("IndexMap<{}, {}>", K::value_type_name(), V::value_type_name()).into()
+    }
+}
+
+/// `Vec<u8>` as serde bytes, for `Bytes` and `Value::Bytes`.
+pub(crate) mod bytes {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    use ::serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(v)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> de::Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("bytes")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        // self-describing formats without bytes, e.g. json, write a list of ints
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
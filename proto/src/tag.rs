@@ -1,5 +1,81 @@
-use crate::prelude::{Uuid, CoreTag};
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::any::{Any, type_name};
 
-pub trait Tag : CoreTag {
+use crate::prelude::{Uuid, CoreTag, TagMeta, QueryValue};
+
+pub trait Tag : CoreTag + Any {
     fn parent(&self) -> Option<&Uuid>;
-}
\ No newline at end of file
+
+    /// Name of the carried value type for display, None for tags without a single value.
+    fn value_type_name(&self) -> Option<Cow<'static, str>> {
+        None
+    }
+
+    /// None for tag types without metadata.
+    fn meta(&self) -> Option<&TagMeta> {
+        None
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut TagMeta> {
+        None
+    }
+
+    /// The value as a `QueryValue`, None for tags that can't be queried.
+    fn as_query(&self) -> Option<&dyn QueryValue> {
+        None
+    }
+}
+
+/// Strip module paths from a type name, `alloc::vec::Vec<alloc::string::String>` becomes `Vec<String>`.
+pub fn short_type_name(name: &str) -> String {
+    let mut short = String::with_capacity(name.len());
+    let mut segment = String::new();
+    for c in name.chars() {
+        match c {
+            ':' => segment.clear(),
+            '<' | '>' | ',' | ' ' | '(' | ')' | '[' | ']' | ';' | '&' => {
+                short.push_str(&segment);
+                segment.clear();
+                short.push(c);
+            },
+            c => segment.push(c),
+        }
+    }
+    short.push_str(&segment);
+    short
+}
+
+pub(crate) fn value_type_name<V: ?Sized>() -> Cow<'static, str> {
+    short_type_name(type_name::<V>()).into()
+}
+
+macro_rules! impl_dyn_tag_downcast {
+    ($($dyn_type: ty),* $(,)?) => {
+        $(
+            impl $dyn_type {
+                pub fn is<T: Tag>(&self) -> bool {
+                    (self as &dyn Any).is::<T>()
+                }
+
+                pub fn downcast_ref<T: Tag>(&self) -> Option<&T> {
+                    (self as &dyn Any).downcast_ref::<T>()
+                }
+
+                /// The value of a `ValTag<V>`, None if the tag is of another type.
+                pub fn val<V: core::fmt::Debug + 'static>(&self) -> Option<&V> {
+                    self.downcast_ref::<$crate::prelude::ValTag<V>>().map(|x| &x.val)
+                }
+            }
+        )*
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) use impl_dyn_tag_downcast;
+
+impl_dyn_tag_downcast!(
+    dyn Tag,
+    dyn Tag + Send,
+    dyn Tag + Send + Sync,
+);
@@ -1,3 +1,332 @@
-use crate::prelude::Tag;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 
-pub struct Tags(Vec<Box<dyn Tag>>);
\ No newline at end of file
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, Tag, IndexMap, IndexSet, ValidationReport, Timestamp};
+
+#[cfg(feature = "std")]
+use crate::meta::now;
+
+#[derive(Debug, Snafu)]
+pub enum TagsError<T: ?Sized + Tag> {
+    #[snafu(display("Not found: `{}`", uuid))]
+    NotFound { uuid: Uuid },
+    /// The edited tag is taken out of the collection and handed back.
+    #[snafu(display("Already exists: `{}`", uuid))]
+    AlreadyExists { uuid: Uuid, tag: Box<T> },
+}
+
+pub type TagsResult<T, E = dyn Tag> = core::result::Result<T, TagsError<E>>;
+
+/// In-memory tag collection keyed by uuid.
+///
+/// The parent/children indexes are derived from `Tag::parent()` and kept in
+/// sync by `insert` and `remove`. A tag whose parent is not in the collection
+/// is still indexed under that parent uuid, it is just not a root.
+#[derive(Debug)]
+pub struct Tags<T: ?Sized + Tag = dyn Tag> {
+    tags: IndexMap<Uuid, Box<T>>,
+    roots: IndexSet<Uuid>,
+    children: IndexMap<Uuid, IndexSet<Uuid>>,
+}
+
+impl<T: ?Sized + Tag> Clone for Tags<T>
+    where Box<T>: Clone
+{
+    fn clone(&self) -> Self {
+        Self {
+            tags: self.tags.clone(),
+            roots: self.roots.clone(),
+            children: self.children.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized + Tag> Default for Tags<T> {
+    fn default() -> Self {
+        Self {
+            tags: IndexMap::default(),
+            roots: IndexSet::default(),
+            children: IndexMap::default(),
+        }
+    }
+}
+
+impl<T: ?Sized + Tag> Tags<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.tags.contains_key(uuid)
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&T> {
+        self.tags.get(uuid).map(|x| x.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tags.values().map(|x| x.as_ref())
+    }
+
+    pub fn uuids(&self) -> impl Iterator<Item = &Uuid> {
+        self.tags.keys()
+    }
+
+    /// Insert a tag, returning the previous tag with the same uuid if any.
+    ///
+    /// A replaced tag keeps its position in iteration order.
+    pub fn insert(&mut self, tag: Box<T>) -> Option<Box<T>> {
+        let uuid = *tag.uuid();
+        if let Some(old) = self.tags.get(&uuid) {
+            let parent = old.parent().copied();
+            self.unindex(&uuid, parent.as_ref());
+        }
+        self.index(&uuid, tag.parent());
+        self.tags.insert(uuid, tag)
+    }
+
+    /// Insert as an edit, stamping the tag's metadata, see `TagMeta::stamp`.
+    ///
+    /// `insert` leaves metadata alone, which is what loading stored tags needs.
+    #[cfg(feature = "std")]
+    pub fn upsert(&mut self, tag: Box<T>) -> Option<Box<T>> {
+        self.upsert_at(tag, now())
+    }
+
+    pub fn upsert_at(&mut self, mut tag: Box<T>, at: Timestamp) -> Option<Box<T>> {
+        let old = self.get(tag.uuid()).and_then(|x| x.meta()).cloned();
+        if let Some(meta) = tag.meta_mut() {
+            meta.stamp(old.as_ref(), at);
+        }
+        self.insert(tag)
+    }
+
+    /// Edit a tag in place and set its `modified` time.
+    ///
+    /// Changing the parent is fine, changing the uuid moves the tag to the end.
+    /// A uuid already used by another tag fails with `AlreadyExists`.
+    #[cfg(feature = "std")]
+    pub fn update<F: FnOnce(&mut T)>(&mut self, uuid: &Uuid, edit: F) -> TagsResult<(), T> {
+        self.update_at(uuid, now(), edit)
+    }
+
+    pub fn update_at<F: FnOnce(&mut T)>(&mut self, uuid: &Uuid, at: Timestamp, edit: F) -> TagsResult<(), T> {
+        let tag = self.tags.get_mut(uuid).context(NotFoundSnafu { uuid: *uuid })?;
+        let old_parent = tag.parent().copied();
+        edit(tag);
+        if let Some(meta) = tag.meta_mut() {
+            meta.modified = Some(at);
+        }
+        let (new_uuid, new_parent) = (*tag.uuid(), tag.parent().copied());
+        if new_uuid != *uuid {
+            self.unindex(uuid, old_parent.as_ref());
+            let tag = self.tags.shift_remove(uuid).context(NotFoundSnafu { uuid: *uuid })?;
+            ensure!(!self.tags.contains_key(&new_uuid), AlreadyExistsSnafu { uuid: new_uuid, tag });
+            self.insert(tag);
+        } else if new_parent != old_parent {
+            self.unindex(uuid, old_parent.as_ref());
+            self.index(uuid, new_parent.as_ref());
+        }
+        Ok(())
+    }
+
+    /// Remove a tag, its children are kept and still indexed under its uuid.
+    pub fn remove(&mut self, uuid: &Uuid) -> Option<Box<T>> {
+        let tag = self.tags.shift_remove(uuid)?;
+        self.unindex(uuid, tag.parent());
+        Some(tag)
+    }
+
+    fn index(&mut self, uuid: &Uuid, parent: Option<&Uuid>) {
+        match parent {
+            Some(parent) => {
+                self.children.entry(*parent).or_default().insert(*uuid);
+            },
+            None => {
+                self.roots.insert(*uuid);
+            },
+        }
+    }
+
+    fn unindex(&mut self, uuid: &Uuid, parent: Option<&Uuid>) {
+        match parent {
+            Some(parent) => {
+                if let Some(siblings) = self.children.get_mut(parent) {
+                    siblings.shift_remove(uuid);
+                    if siblings.is_empty() {
+                        self.children.shift_remove(parent);
+                    }
+                }
+            },
+            None => {
+                self.roots.shift_remove(uuid);
+            },
+        }
+    }
+
+    /// Duplicates can't exist here, only missing parents and cycles are reported.
+    pub fn validate(&self) -> ValidationReport {
+        crate::validate::validate(self.iter())
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &T> {
+        self.roots.iter().filter_map(|x| self.get(x))
+    }
+
+    pub fn parent_of(&self, uuid: &Uuid) -> Option<&T> {
+        self.get(uuid)?.parent().and_then(|x| self.get(x))
+    }
+
+    pub fn children_of(&self, uuid: &Uuid) -> impl Iterator<Item = &T> {
+        self.children.get(uuid)
+            .into_iter()
+            .flat_map(|x| x.iter())
+            .filter_map(|x| self.get(x))
+    }
+
+    /// Parent chain of a tag, nearest first, stops at a missing parent or a cycle.
+    pub fn ancestors(&self, uuid: &Uuid) -> Ancestors<'_, T> {
+        Ancestors {
+            tags: self,
+            next: self.get(uuid).and_then(|x| x.parent()).copied(),
+            visited: IndexSet::from_iter([*uuid]),
+        }
+    }
+
+    /// All tags below a tag in depth-first pre-order, each visited once.
+    pub fn descendants(&self, uuid: &Uuid) -> Descendants<'_, T> {
+        let mut stack: Vec<&Uuid> = self.children.get(uuid)
+            .map(|x| x.iter().collect())
+            .unwrap_or_default();
+        stack.reverse();
+        Descendants {
+            tags: self,
+            stack,
+            visited: IndexSet::from_iter([*uuid]),
+        }
+    }
+}
+
+impl<T: ?Sized + Tag> FromIterator<Box<T>> for Tags<T> {
+    fn from_iter<I: IntoIterator<Item = Box<T>>>(iter: I) -> Self {
+        let mut tags = Self::new();
+        tags.extend(iter);
+        tags
+    }
+}
+
+impl<T: ?Sized + Tag> Extend<Box<T>> for Tags<T> {
+    fn extend<I: IntoIterator<Item = Box<T>>>(&mut self, iter: I) {
+        for tag in iter {
+            self.insert(tag);
+        }
+    }
+}
+
+impl<T: ?Sized + Tag> IntoIterator for Tags<T> {
+    type Item = Box<T>;
+    type IntoIter = indexmap::map::IntoValues<Uuid, Box<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tags.into_values()
+    }
+}
+
+pub struct Ancestors<'a, T: ?Sized + Tag> {
+    tags: &'a Tags<T>,
+    next: Option<Uuid>,
+    visited: IndexSet<Uuid>,
+}
+
+impl<'a, T: ?Sized + Tag> Iterator for Ancestors<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let uuid = self.next.take()?;
+        if !self.visited.insert(uuid) {
+            return None;
+        }
+        let tag = self.tags.get(&uuid)?;
+        self.next = tag.parent().copied();
+        Some(tag)
+    }
+}
+
+pub struct Descendants<'a, T: ?Sized + Tag> {
+    tags: &'a Tags<T>,
+    stack: Vec<&'a Uuid>,
+    visited: IndexSet<Uuid>,
+}
+
+impl<'a, T: ?Sized + Tag> Iterator for Descendants<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(uuid) = self.stack.pop() {
+            if !self.visited.insert(*uuid) {
+                continue;
+            }
+            if let Some(children) = self.tags.children.get(uuid) {
+                self.stack.extend(children.iter().rev());
+            }
+            if let Some(tag) = self.tags.get(uuid) {
+                return Some(tag);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::*;
+    use crate::prelude::{ValTag, TagMeta};
+
+    fn tag(uuid: u128, parent: Option<u128>) -> Box<ValTag<String>> {
+        Box::new(ValTag { uuid: Uuid::from_u128(uuid), parent: parent.map(Uuid::from_u128), val: uuid.to_string(), meta: TagMeta::default() })
+    }
+
+    #[test]
+    fn update_moves_uuid() {
+        let mut tags: Tags<ValTag<String>> = Tags::from_iter([tag(1, None), tag(2, Some(1))]);
+        tags.update_at(&Uuid::from_u128(1), 10, |x| x.uuid = Uuid::from_u128(3)).unwrap();
+        assert!(!tags.contains(&Uuid::from_u128(1)));
+        assert_eq!(tags.get(&Uuid::from_u128(3)).map(|x| x.meta.modified), Some(Some(10)));
+        assert_eq!(tags.roots().map(|x| x.uuid).collect::<Vec<_>>(), [Uuid::from_u128(3)]);
+    }
+
+    #[test]
+    fn update_rejects_taken_uuid() {
+        let mut tags: Tags<ValTag<String>> = Tags::from_iter([tag(1, None), tag(2, Some(1))]);
+        let err = tags.update_at(&Uuid::from_u128(2), 10, |x| x.uuid = Uuid::from_u128(1)).unwrap_err();
+        let TagsError::AlreadyExists { uuid, tag } = err else {
+            panic!("expected AlreadyExists");
+        };
+        assert_eq!(uuid, Uuid::from_u128(1));
+        assert_eq!(tag.val, "2");
+        assert_eq!(tags.get(&Uuid::from_u128(1)).map(|x| x.val.as_str()), Some("1"));
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags.children_of(&Uuid::from_u128(1)).count(), 0);
+        assert!(matches!(tags.update_at(&Uuid::from_u128(2), 10, |_| ()), Err(TagsError::NotFound { .. })));
+    }
+
+    #[test]
+    fn clone_keeps_indexes() {
+        let tags: Tags<ValTag<String>> = Tags::from_iter([tag(1, None), tag(2, Some(1))]);
+        let copy = tags.clone();
+        assert_eq!(copy.children_of(&Uuid::from_u128(1)).count(), 1);
+        assert_eq!(copy.roots().count(), 1);
+    }
+}
@@ -0,0 +1,398 @@
+use std::any::Any;
+use std::fmt::{self, Debug, Display};
+use std::ops::{Range, RangeBounds};
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use snafu::prelude::*;
+
+use crate::prelude::{Uuid, Tag, ValTag, IndexMap};
+
+#[derive(Debug, Snafu)]
+pub enum TemporalError {
+    #[snafu(display("Invalid duration: `{}`", text))]
+    InvalidDuration { text: String },
+}
+
+pub type TemporalResult<T> = std::result::Result<T, TemporalError>;
+
+/// A `TimeDelta` written as an ISO-8601 duration, e.g. `P3DT4H5M6.5S`.
+///
+/// Only weeks, days and time components are accepted, years and months don't
+/// have a fixed length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IsoDuration(pub TimeDelta);
+
+pub type DateTag = ValTag<NaiveDate>;
+pub type DateTimeTag = ValTag<DateTime<Utc>>;
+pub type DurationTag = ValTag<IsoDuration>;
+
+impl From<TimeDelta> for IsoDuration {
+    fn from(v: TimeDelta) -> Self {
+        Self(v)
+    }
+}
+
+impl Display for IsoDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let delta = if self.0 < TimeDelta::zero() {
+            f.write_str("-")?;
+            -self.0
+        } else {
+            self.0
+        };
+        let secs = delta.num_seconds();
+        let nanos = delta.subsec_nanos();
+        let (days, hours, minutes, seconds) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+        f.write_str("P")?;
+        if days > 0 {
+            write!(f, "{}D", days)?;
+        }
+        if days > 0 && hours == 0 && minutes == 0 && seconds == 0 && nanos == 0 {
+            return Ok(());
+        }
+        f.write_str("T")?;
+        if hours > 0 {
+            write!(f, "{}H", hours)?;
+        }
+        if minutes > 0 {
+            write!(f, "{}M", minutes)?;
+        }
+        if seconds > 0 || nanos > 0 || (days == 0 && hours == 0 && minutes == 0) {
+            if nanos > 0 {
+                let fraction = format!("{:09}", nanos);
+                write!(f, "{}.{}S", seconds, fraction.trim_end_matches('0'))?;
+            } else {
+                write!(f, "{}S", seconds)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IsoDuration {
+    type Err = TemporalError;
+
+    fn from_str(text: &str) -> TemporalResult<Self> {
+        parse_duration(text).context(InvalidDurationSnafu { text })
+    }
+}
+
+fn parse_duration(text: &str) -> Option<IsoDuration> {
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None),
+    };
+    // `T` has to be followed by a time component
+    if time == Some("") || (date.is_empty() && time.is_none()) {
+        return None;
+    }
+    let mut total = TimeDelta::zero();
+    for (number, unit) in duration_components(date, &['W', 'D'])? {
+        let number: i64 = number.parse().ok()?;
+        let delta = match unit {
+            'W' => TimeDelta::try_weeks(number)?,
+            _ => TimeDelta::try_days(number)?,
+        };
+        total = total.checked_add(&delta)?;
+    }
+    for (number, unit) in duration_components(time.unwrap_or(""), &['H', 'M', 'S'])? {
+        let delta = match unit {
+            'H' => TimeDelta::try_hours(number.parse().ok()?)?,
+            'M' => TimeDelta::try_minutes(number.parse().ok()?)?,
+            _ => {
+                let (seconds, fraction) = number.split_once('.').unwrap_or((number, ""));
+                if fraction.len() > 9 || !fraction.chars().all(|x| x.is_ascii_digit()) {
+                    return None;
+                }
+                let nanos: u32 = if fraction.is_empty() {
+                    0
+                } else {
+                    format!("{:0<9}", fraction).parse().ok()?
+                };
+                TimeDelta::new(seconds.parse().ok()?, nanos)?
+            },
+        };
+        total = total.checked_add(&delta)?;
+    }
+    Some(IsoDuration(if negative { -total } else { total }))
+}
+
+// splits `3D` style components, units must appear in the given order, at most once each
+fn duration_components<'a>(text: &'a str, units: &[char]) -> Option<Vec<(&'a str, char)>> {
+    let mut components = Vec::new();
+    let mut next_unit = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        if c.is_ascii_digit() || c == '.' {
+            continue;
+        }
+        let position = units[next_unit..].iter().position(|x| *x == c)?;
+        let number = &text[start..index];
+        if number.is_empty() || (number.contains('.') && c != 'S') {
+            return None;
+        }
+        components.push((number, c));
+        next_unit += position + 1;
+        start = index + c.len_utf8();
+    }
+    if start != text.len() {
+        return None;
+    }
+    Some(components)
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for IsoDuration {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IsoDuration {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A value that sits at a point in time, dates are taken at midnight and datetimes in UTC.
+pub trait Temporal {
+    fn instant(&self) -> NaiveDateTime;
+}
+
+impl Temporal for NaiveDate {
+    fn instant(&self) -> NaiveDateTime {
+        self.and_time(NaiveTime::MIN)
+    }
+}
+
+impl Temporal for NaiveDateTime {
+    fn instant(&self) -> NaiveDateTime {
+        *self
+    }
+}
+
+impl Temporal for DateTime<Utc> {
+    fn instant(&self) -> NaiveDateTime {
+        self.naive_utc()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bucket {
+    Decade,
+    Year,
+    Month,
+    Day,
+}
+
+impl Bucket {
+    pub fn start_of<T: Temporal + ?Sized>(&self, value: &T) -> NaiveDate {
+        let date = value.instant().date();
+        let start = match self {
+            Self::Decade => NaiveDate::from_ymd_opt(date.year() - date.year().rem_euclid(10), 1, 1),
+            Self::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1),
+            Self::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1),
+            Self::Day => Some(date),
+        };
+        // decade and year starts can fall before the earliest supported date
+        start.unwrap_or(NaiveDate::MIN)
+    }
+
+    /// Half-open range of the bucket `value` falls in, `Bucket::Decade.range_of(&date)` is "the 1990s" for a 1990s date.
+    pub fn range_of<T: Temporal + ?Sized>(&self, value: &T) -> Range<NaiveDateTime> {
+        let start = self.start_of(value);
+        let end = match self {
+            Self::Decade => start.checked_add_months(Months::new(120)),
+            Self::Year => start.checked_add_months(Months::new(12)),
+            Self::Month => start.checked_add_months(Months::new(1)),
+            Self::Day => start.succ_opt(),
+        };
+        start.instant()..end.unwrap_or(NaiveDate::MAX).instant()
+    }
+}
+
+/// A tag that may carry a temporal value, the dyn tag impls look for
+/// `DateTag`, `DateTimeTag` and `ValTag<NaiveDateTime>`.
+pub trait TemporalTag : Tag {
+    fn instant(&self) -> Option<NaiveDateTime>;
+}
+
+impl<V: Temporal + Debug + 'static> TemporalTag for ValTag<V> {
+    fn instant(&self) -> Option<NaiveDateTime> {
+        Some(self.val.instant())
+    }
+}
+
+fn instant_of(tag: &dyn Any) -> Option<NaiveDateTime> {
+    if let Some(tag) = tag.downcast_ref::<DateTag>() {
+        return Some(tag.val.instant());
+    }
+    if let Some(tag) = tag.downcast_ref::<DateTimeTag>() {
+        return Some(tag.val.instant());
+    }
+    tag.downcast_ref::<ValTag<NaiveDateTime>>().map(|x| x.val)
+}
+
+macro_rules! impl_dyn_temporal_tag {
+    ($($dyn_type: ty),* $(,)?) => {
+        $(
+            impl TemporalTag for $dyn_type {
+                fn instant(&self) -> Option<NaiveDateTime> {
+                    instant_of(self)
+                }
+            }
+        )*
+    }
+}
+
+impl_dyn_temporal_tag!(
+    dyn Tag,
+    dyn Tag + Send,
+    dyn Tag + Send + Sync,
+);
+
+#[cfg(feature = "serde")]
+impl_dyn_temporal_tag!(
+    dyn crate::serde::SerdeTag,
+);
+
+/// Tags with a temporal value in `range`.
+pub fn in_range<'a, T, I, R>(tags: I, range: R) -> impl Iterator<Item = &'a T>
+    where
+        T: ?Sized + TemporalTag + 'a,
+        I: IntoIterator<Item = &'a T>,
+        R: RangeBounds<NaiveDateTime> + 'a,
+{
+    tags.into_iter()
+        .filter(move |x| x.instant().map(|x| range.contains(&x)).unwrap_or(false))
+}
+
+/// Uuids of tags grouped by the start of their bucket, in chronological order.
+///
+/// Tags without a temporal value are skipped.
+pub fn bucketed<'a, T, I>(tags: I, bucket: Bucket) -> IndexMap<NaiveDate, Vec<Uuid>>
+    where
+        T: ?Sized + TemporalTag + 'a,
+        I: IntoIterator<Item = &'a T>,
+{
+    let mut buckets: IndexMap<NaiveDate, Vec<Uuid>> = IndexMap::new();
+    for tag in tags {
+        if let Some(instant) = tag.instant() {
+            buckets.entry(bucket.start_of(&instant))
+                .or_default()
+                .push(*tag.uuid());
+        }
+    }
+    buckets.sort_keys();
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn duration(text: &str) -> IsoDuration {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn durations_parse() {
+        let expected = TimeDelta::days(3) + TimeDelta::hours(4) + TimeDelta::minutes(5) + TimeDelta::milliseconds(6500);
+        assert_eq!(duration("P3DT4H5M6.5S").0, expected);
+        assert_eq!(duration("P2W").0, TimeDelta::days(14));
+        assert_eq!(duration("P1W2D").0, TimeDelta::days(9));
+        assert_eq!(duration("PT0S").0, TimeDelta::zero());
+        assert_eq!(duration("PT0.000000001S").0, TimeDelta::nanoseconds(1));
+        assert_eq!(duration("-PT1H30M").0, -TimeDelta::minutes(90));
+    }
+
+    #[test]
+    fn durations_display() {
+        for text in ["P3DT4H5M6.5S", "P1D", "PT0S", "PT1H", "PT1M1S", "PT0.25S", "-PT1H30M", "-P2DT1S"] {
+            assert_eq!(duration(text).to_string(), text);
+        }
+        assert_eq!(duration("P2W").to_string(), "P14D");
+        assert_eq!(duration("PT36H").to_string(), "P1DT12H");
+        assert_eq!(duration("PT90S").to_string(), "PT1M30S");
+    }
+
+    #[test]
+    fn malformed_durations() {
+        let malformed = [
+            "", "P", "PT", "P1DT", "T1H", "1D", "--P1D",
+            "P1", "PT1H2", "PD", "PTS", "P1Y", "P1M", "PT1D", "P1D1W", "PT1S1M", "P1D1D",
+            "P-1D", "PT-5S", "PT+5S", "-P-1D",
+            "PT1.5M", "P1.5D", "PT1.1234567891S", "PT.5S", "p1d", "P1DT1h",
+        ];
+        for text in malformed {
+            assert!(text.parse::<IsoDuration>().is_err(), "{}", text);
+        }
+        assert!(format!("P{}D", i64::MAX).parse::<IsoDuration>().is_err());
+    }
+
+    #[test]
+    fn bucket_ranges() {
+        let day = |x: NaiveDate| x.instant();
+        assert_eq!(Bucket::Decade.range_of(&date(1999, 12, 31)), day(date(1990, 1, 1))..day(date(2000, 1, 1)));
+        assert_eq!(Bucket::Decade.range_of(&date(2000, 1, 1)), day(date(2000, 1, 1))..day(date(2010, 1, 1)));
+        assert_eq!(Bucket::Decade.range_of(&date(-5, 6, 1)), day(date(-10, 1, 1))..day(date(0, 1, 1)));
+        assert_eq!(Bucket::Year.range_of(&date(2024, 12, 31)), day(date(2024, 1, 1))..day(date(2025, 1, 1)));
+        assert_eq!(Bucket::Month.range_of(&date(2024, 2, 29)), day(date(2024, 2, 1))..day(date(2024, 3, 1)));
+        assert_eq!(Bucket::Month.range_of(&date(2024, 12, 1)), day(date(2024, 12, 1))..day(date(2025, 1, 1)));
+        assert_eq!(Bucket::Day.range_of(&date(2024, 2, 28)), day(date(2024, 2, 28))..day(date(2024, 2, 29)));
+
+        let last = date(1999, 12, 31).and_hms_opt(23, 59, 59).unwrap();
+        let range = Bucket::Year.range_of(&last);
+        assert!(range.contains(&last));
+        assert!(!range.contains(&day(date(2000, 1, 1))));
+        assert!(range.contains(&day(date(1999, 1, 1))));
+    }
+
+    fn tags() -> Vec<Box<dyn Tag>> {
+        let at = |uuid: u128, val: NaiveDate| Box::new(ValTag { uuid: Uuid::from_u128(uuid), parent: None, val, meta: Default::default() }) as Box<dyn Tag>;
+        vec![
+            at(1, date(2001, 5, 1)),
+            Box::new(ValTag { uuid: Uuid::from_u128(2), parent: None, val: date(1999, 12, 31).and_hms_opt(23, 0, 0).unwrap().and_utc(), meta: Default::default() }),
+            at(3, date(1990, 1, 1)),
+            Box::new(ValTag { uuid: Uuid::from_u128(4), parent: None, val: "2000".to_string(), meta: Default::default() }),
+            at(5, date(2000, 1, 1)),
+        ]
+    }
+
+    #[test]
+    fn tags_in_range() {
+        let tags = tags();
+        let nineties = Bucket::Decade.range_of(&date(1995, 1, 1));
+        let found: Vec<_> = in_range(tags.iter().map(|x| x.as_ref()), nineties)
+            .map(|x| x.uuid().as_u128())
+            .collect();
+        assert_eq!(found, [2, 3]);
+        let found: Vec<_> = in_range(tags.iter().map(|x| x.as_ref()), date(2000, 1, 1).instant()..)
+            .map(|x| x.uuid().as_u128())
+            .collect();
+        assert_eq!(found, [1, 5]);
+    }
+
+    #[test]
+    fn tags_bucketed() {
+        let tags = tags();
+        let buckets = bucketed(tags.iter().map(|x| x.as_ref()), Bucket::Decade);
+        let buckets: Vec<_> = buckets.into_iter()
+            .map(|(start, uuids)| (start, uuids.into_iter().map(|x| x.as_u128()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(buckets, [(date(1990, 1, 1), vec![2, 3]), (date(2000, 1, 1), vec![1, 5])]);
+        assert_eq!(bucketed(tags.iter().map(|x| x.as_ref()), Bucket::Year).len(), 4);
+    }
+}
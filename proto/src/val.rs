@@ -1,15 +1,22 @@
-use std::fmt::Debug;
+use alloc::borrow::Cow;
+use core::fmt::Debug;
 use derive_builder::Builder;
 
-use crate::prelude::{Uuid, CoreTag, Tag};
+use crate::prelude::{Uuid, CoreTag, Tag, TagMeta, QueryValue, path_uuid, parent_path_uuid};
+use crate::query::query_value;
+use crate::tag::value_type_name;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Builder)]
+#[cfg_attr(not(feature = "std"), builder(no_std))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct ValTag<V> {
     pub uuid: Uuid,
     #[builder(setter(into, strip_option), default)]
     pub parent: Option<Uuid>,
     pub val: V,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meta: TagMeta,
 }
 
 impl<V> CoreTag for ValTag<V>
@@ -25,9 +32,77 @@ impl<V> CoreTag for ValTag<V>
 }
 
 impl<V> Tag for ValTag<V>
-    where V: Debug
+    where V: Debug + 'static
 {
     fn parent(&self) -> Option<&Uuid> {
         self.parent.as_ref()
     }
+
+    fn value_type_name(&self) -> Option<Cow<'static, str>> {
+        Some(value_type_name::<V>())
+    }
+
+    fn meta(&self) -> Option<&TagMeta> {
+        Some(&self.meta)
+    }
+
+    fn meta_mut(&mut self) -> Option<&mut TagMeta> {
+        Some(&mut self.meta)
+    }
+
+    fn as_query(&self) -> Option<&dyn QueryValue> {
+        query_value(&self.val)
+    }
+}
+
+impl<V: Clone> ValTagBuilder<V> {
+    /// Use the stable uuid of `path` within `namespace`, see `tag_core::identity`.
+    pub fn uuid_from_path(&mut self, namespace: &Uuid, path: &str) -> &mut Self {
+        self.uuid(path_uuid(namespace, path))
+    }
+
+    /// Set both uuid and parent from `path`, so the tag ends up under the tag of its parent path.
+    pub fn path(&mut self, namespace: &Uuid, path: &str) -> &mut Self {
+        if let Some(parent) = parent_path_uuid(namespace, path) {
+            self.parent(parent);
+        }
+        self.uuid_from_path(namespace, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::*;
+    use crate::prelude::namespace_uuid;
+
+    fn at(path: &str) -> ValTag<String> {
+        ValTagBuilder::default()
+            .path(&namespace_uuid("library"), path)
+            .val(path.to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn builder_paths() {
+        let jazz = at("music/jazz");
+        assert_eq!(jazz.uuid, uuid::uuid!("086a18d6-7b7c-58c6-a5ca-16868f560bef"));
+        assert_eq!(jazz.parent, Some(at("music").uuid));
+        assert_eq!(at("music").parent, None);
+        assert_eq!(at("/music//jazz").uuid, jazz.uuid);
+        assert_ne!(at("music/rock").uuid, jazz.uuid);
+    }
+
+    #[test]
+    fn builder_uuid_from_path() {
+        let tag: ValTag<u32> = ValTagBuilder::default()
+            .uuid_from_path(&namespace_uuid("library"), "music/jazz")
+            .val(1)
+            .build()
+            .unwrap();
+        assert_eq!(tag.uuid, at("music/jazz").uuid);
+        assert_eq!(tag.parent, None);
+    }
 }
@@ -0,0 +1,100 @@
+use alloc::vec::Vec;
+
+use crate::prelude::{Uuid, Tag, IndexMap};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Two entries share a uuid, `first` and `second` are their positions in the input.
+    DuplicateUuid { uuid: Uuid, first: usize, second: usize },
+    MissingParent { uuid: Uuid, parent: Uuid },
+    /// Uuids along the parent chain, each one's parent is the next, the last one's is the first.
+    Cycle { path: Vec<Uuid> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn duplicates(&self) -> impl Iterator<Item = &Violation> {
+        self.violations.iter().filter(|x| matches!(x, Violation::DuplicateUuid { .. }))
+    }
+
+    pub fn missing_parents(&self) -> impl Iterator<Item = &Violation> {
+        self.violations.iter().filter(|x| matches!(x, Violation::MissingParent { .. }))
+    }
+
+    pub fn cycles(&self) -> impl Iterator<Item = &Violation> {
+        self.violations.iter().filter(|x| matches!(x, Violation::Cycle { .. }))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Walking,
+    Done,
+}
+
+/// Check uuid uniqueness, parent existence and parent chain acyclicity.
+///
+/// When a uuid is duplicated, the first entry is the one used for parent checks.
+pub fn validate<'a, T, I>(tags: I) -> ValidationReport
+    where
+        T: ?Sized + Tag + 'a,
+        I: IntoIterator<Item = &'a T>,
+{
+    let mut violations = Vec::new();
+    let mut parents: IndexMap<Uuid, (usize, Option<Uuid>)> = IndexMap::default();
+    for (index, tag) in tags.into_iter().enumerate() {
+        if let Some((first, _)) = parents.get(tag.uuid()) {
+            violations.push(Violation::DuplicateUuid {
+                uuid: *tag.uuid(),
+                first: *first,
+                second: index,
+            });
+            continue;
+        }
+        parents.insert(*tag.uuid(), (index, tag.parent().copied()));
+    }
+    for (uuid, (_, parent)) in parents.iter() {
+        if let Some(parent) = parent {
+            if !parents.contains_key(parent) {
+                violations.push(Violation::MissingParent {
+                    uuid: *uuid,
+                    parent: *parent,
+                });
+            }
+        }
+    }
+    let mut visits: IndexMap<Uuid, Visit> = IndexMap::default();
+    for uuid in parents.keys() {
+        let mut path = Vec::new();
+        let mut current = Some(*uuid);
+        while let Some(uuid) = current {
+            match visits.get(&uuid) {
+                Some(Visit::Done) => break,
+                Some(Visit::Walking) => {
+                    let start = path.iter().position(|x| *x == uuid).unwrap_or_default();
+                    violations.push(Violation::Cycle {
+                        path: path[start..].to_vec(),
+                    });
+                    break;
+                },
+                None => {
+                    visits.insert(uuid, Visit::Walking);
+                    path.push(uuid);
+                    current = parents.get(&uuid).and_then(|(_, parent)| *parent);
+                },
+            }
+        }
+        for uuid in path {
+            visits.insert(uuid, Visit::Done);
+        }
+    }
+    ValidationReport { violations }
+}
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use crate::prelude::ValTag;
 
 pub type VecTag<V> = ValTag<Vec<V>>;